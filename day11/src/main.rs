@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 struct Grid {
     grid: Vec<Vec<char>>,
 }
@@ -38,25 +40,14 @@ impl Grid {
         self.grid
             .iter()
             .enumerate()
-            .filter_map(|(y, row)| {
-                if row.iter().all(|&c| c == '.') {
-                    Some(y)
-                } else {
-                    None
-                }
-            })
+            .filter(|(_, row)| row.iter().all(|&c| c == '.'))
+            .map(|(y, _)| y)
             .collect()
     }
 
     fn expanded_cols(&self) -> Vec<usize> {
         (0..self.width())
-            .filter_map(|x| {
-                if self.grid.iter().all(|row| row[x] == '.') {
-                    Some(x)
-                } else {
-                    None
-                }
-            })
+            .filter(|&x| self.grid.iter().all(|row| row[x] == '.'))
             .collect()
     }
 
@@ -83,58 +74,64 @@ impl Grid {
         //dbg!(&expanded_rows, &expanded_cols);
     }
 
-    fn go(&self, expansion: isize) -> isize {
-        let expanded_rows = self.expanded_rows();
-        let expanded_cols = self.expanded_cols();
-
-        let galaxies = self
-            .iter_coords()
-            .filter_map(|(x, y)| match self.get((x, y)) {
-                Some('#') => Some((x, y)),
-                _ => None,
-            })
-            .collect::<Vec<_>>();
-
-        let pair_iterator = galaxies.iter().enumerate().flat_map(|(i, &x)| {
-            galaxies
-                .iter()
-                .enumerate()
-                .filter(move |&(j, _)| i < j)
-                .map(move |(_, &y)| (x, y))
-        });
-
-        let pair_distance = pair_iterator.map(|(from, to)| {
-            let (fromx, fromy) = from;
-            let (tox, toy) = to;
-
-            let dx = ((fromx as isize) - (tox as isize)).abs();
-            let dy = ((fromy as isize) - (toy as isize)).abs();
-
-            // How many empty columns/rows do we cross?
-            // Note: The start and the end are not empty because there is a galaxy there.
-
-            let range_help = |a, b| {
-                if a < b {
-                    a..b
-                } else {
-                    b..a
-                }
-            };
+    fn galaxies(&self) -> Vec<(usize, usize)> {
+        self.iter_coords()
+            .filter(|&coord| self.get(coord) == Some(&'#'))
+            .collect()
+    }
 
-            let empty_cols = (range_help(fromx, tox))
-                .filter(|x| expanded_cols.contains(x))
-                .count() as isize;
-            let empty_rows = (range_help(fromy, toy))
-                .filter(|y| expanded_rows.contains(y))
-                .count() as isize;
+    // Sum of the distances between all pairs of galaxies, where every empty
+    // row becomes row_expansion rows and every empty column col_expansion
+    // columns
+    fn go(&self, row_expansion: u128, col_expansion: u128) -> u128 {
+        let rows = self.expanded_rows().into_iter().map(|y| (y, row_expansion)).collect::<Vec<_>>();
+        let cols = self.expanded_cols().into_iter().map(|x| (x, col_expansion)).collect::<Vec<_>>();
+        self.go_with(&rows, &cols)
+    }
 
-            //dbg!(&(from,to), &dx, dy, empty_cols, empty_rows, expansion);
+    // Same, but each empty row and column has its own factor, given as
+    // (line, factor) in line order.  Distances split into x and y parts,
+    // which are summed separately.
+    fn go_with(&self, rows: &[(usize, u128)], cols: &[(usize, u128)]) -> u128 {
+        let galaxies = self.galaxies();
+        let xs = galaxies.iter().map(|(x, _)| *x).collect::<Vec<_>>();
+        let ys = galaxies.iter().map(|(_, y)| *y).collect::<Vec<_>>();
 
-            dx + dy + (empty_cols + empty_rows) * (expansion-1)
-        });
+        axis_distances(xs, cols) + axis_distances(ys, rows)
+    }
+}
 
-        pair_distance.sum::<isize>()
+// Sum of |a - b| over all pairs of coordinates on one axis, after expanding
+// the empty lines, given as (line, factor) in line order.  A coordinate moves
+// by factor - 1 for each empty line before it, so it lands at itself minus
+// the number of those lines plus the sum of their factors.  Once the
+// positions are sorted, each one is further along than every position before
+// it, by its own value times their number minus their sum.
+fn axis_distances(coords: Vec<usize>, empty: &[(usize, u128)]) -> u128 {
+    let mut factors = vec![0];
+    for (_, f) in empty {
+        factors.push(factors.last().unwrap() + f);
     }
+
+    let mut positions = coords
+        .into_iter()
+        .map(|c| {
+            let empties = empty.partition_point(|(e, _)| *e < c);
+            c as u128 - empties as u128 + factors[empties]
+        })
+        .collect::<Vec<_>>();
+    positions.sort();
+
+    let mut before = 0;
+    positions
+        .iter()
+        .enumerate()
+        .map(|(i, p)| {
+            let distance = p * i as u128 - before;
+            before += p;
+            distance
+        })
+        .sum()
 }
 
 impl std::fmt::Debug for Grid {
@@ -169,11 +166,56 @@ fn main() {
     dbg!(&galaxies);
     */
 
-    let p1 = grid.go(2);
+    // --rows=N and --cols=N say how many lines each empty row and column
+    // becomes, and --row=Y:N and --col=X:N set it for a single one.  Rows
+    // count down from the top of the input, and both count from 0.
+    let (mut rows, mut cols) = (None, None);
+    let (mut row_factors, mut col_factors) = (HashMap::new(), HashMap::new());
+    let line_factor = |s: &str| -> (usize, u128) {
+        let (line, n) = s.split_once(':').unwrap_or_else(|| panic!("Expected LINE:N, got {s}"));
+        (
+            line.parse::<usize>().unwrap_or_else(|e| panic!("{e}")),
+            n.parse::<u128>().unwrap_or_else(|e| panic!("{e}")),
+        )
+    };
+    for arg in std::env::args().skip(1) {
+        match arg.split_once('=') {
+            Some(("--rows", n)) => rows = Some(n.parse::<u128>().unwrap_or_else(|e| panic!("{e}"))),
+            Some(("--cols", n)) => cols = Some(n.parse::<u128>().unwrap_or_else(|e| panic!("{e}"))),
+            Some(("--row", s)) => {
+                let (y, n) = line_factor(s);
+                let row = grid.height().checked_sub(y + 1);
+                let row = row.filter(|r| grid.expanded_rows().contains(r));
+                row_factors.insert(row.unwrap_or_else(|| panic!("Row {y} isn't empty")), n);
+            }
+            Some(("--col", s)) => {
+                let (x, n) = line_factor(s);
+                assert!(grid.expanded_cols().contains(&x), "Column {x} isn't empty");
+                col_factors.insert(x, n);
+            }
+            _ => panic!("Unknown option {arg}"),
+        }
+    }
+    if rows.is_some() || cols.is_some() || !row_factors.is_empty() || !col_factors.is_empty() {
+        let factors = |empty: Vec<usize>, all: Option<u128>, single: &HashMap<usize, u128>| {
+            empty
+                .into_iter()
+                .map(|l| (l, single.get(&l).copied().unwrap_or(all.unwrap_or(2))))
+                .collect::<Vec<_>>()
+        };
+        let custom = grid.go_with(
+            &factors(grid.expanded_rows(), rows, &row_factors),
+            &factors(grid.expanded_cols(), cols, &col_factors),
+        );
+        println!("Custom: {custom}");
+        return;
+    }
+
+    let p1 = grid.go(2, 2);
 
     println!("Part 1: {p1}");
 
-    let p2 = grid.go(1000000);
+    let p2 = grid.go(1000000, 1000000);
 
     println!("Part 2: {p2}");
 
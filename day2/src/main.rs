@@ -25,6 +25,15 @@ impl Color {
         vec![Color::Red, Color::Green, Color::Blue]
     }
 
+    // Position of this color in `values()`, used to index `Counts`
+    fn index(&self) -> usize {
+        match self {
+            Color::Red => 0,
+            Color::Green => 1,
+            Color::Blue => 2,
+        }
+    }
+
     fn from_string(s: &str) -> Result<Color, &'static str> {
         match s.to_lowercase().as_str() {
             "red" => Ok(Color::Red),
@@ -35,15 +44,54 @@ impl Color {
     }
 }
 
+type Draw = Vec<(u32, Color)>;
+
+// Cube counts indexed by `Color::index`
+type Counts = [u32; 3];
+
+fn counts_to_config(counts: &Counts) -> MarbleConfig {
+    Color::values().into_iter().zip(counts.iter().copied()).collect()
+}
+
+fn draw_counts(draw: &Draw) -> Counts {
+    let mut counts = [0; 3];
+    for (num, color) in draw {
+        counts[color.index()] += num;
+    }
+    counts
+}
+
 struct GameInfo {
     id: u32,
-    obs: Vec<(u32, Color)>,
+    draws: Vec<Draw>,
+}
+
+// A single color in a single draw that exceeds what the bag holds
+#[derive(Debug)]
+struct Violation {
+    draw: usize,
+    color: Color,
+    num: u32,
+    over: u32,
+}
+
+impl std::fmt::Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "draw {} has {} {:?} ({} over)",
+            self.draw + 1,
+            self.num,
+            self.color,
+            self.over
+        )
+    }
 }
 
 impl GameInfo {
-    // Read a line, and convert to a vector of (u32, string) tuples
+    // Read a line, and convert to a vector of draws, each a vector of (u32, Color) tuples
     fn from_string(s: &str) -> GameInfo {
-        let mut split = s.splitn(3, " ");
+        let mut split = s.splitn(3, ' ');
         assert!(split.next() == Some("Game"));
 
         let id = split
@@ -53,41 +101,200 @@ impl GameInfo {
             .parse::<u32>()
             .expect("Expected game id to be a number");
 
-        let obs_str = split
+        let parse_obs = |s: &str| match s.splitn(2, ' ').take(2).collect::<Vec<_>>().as_slice() {
+            [num, color] => (
+                num.parse::<u32>().expect("Expected number"),
+                Color::from_string(color.trim_end_matches(':')).unwrap(),
+            ),
+            _ => panic!("Expected number and observation"),
+        };
+
+        let draws = split
             .next()
             .expect("observations")
             .split("; ")
-            .map(|str| str.split(", "))
-            .flatten();
-
-        let obs = obs_str.map(
-            |s| match s.splitn(2, " ").take(2).collect::<Vec<_>>().as_slice() {
-                [num, color] => (
-                    num.parse::<u32>().expect("Expected number"),
-                    Color::from_string(color.trim_end_matches(':')).unwrap(),
-                ),
-                _ => panic!("Expected number and observation"),
-            },
-        );
+            .map(|draw| draw.split(", ").map(parse_obs).collect())
+            .collect();
 
-        //obs.for_each(|s| println!("{:?}", s));
+        GameInfo { id, draws }
+    }
 
-        GameInfo {
-            id,
-            obs: obs.collect(),
-        }
+    // All observations, regardless of which draw they came from
+    fn obs(&self) -> impl Iterator<Item = &(u32, Color)> {
+        self.draws.iter().flatten()
+    }
+
+    // Every draw and color that exceeds the bag, with the amount over the limit
+    fn explain(&self, marbles: &MarbleConfig) -> Vec<Violation> {
+        self.draws
+            .iter()
+            .enumerate()
+            .flat_map(|(i, draw)| {
+                draw.iter().filter_map(move |(num, color)| {
+                    let limit = marbles.get(color).copied().unwrap_or(0);
+                    (*num > limit).then(|| Violation {
+                        draw: i,
+                        color: color.clone(),
+                        num: *num,
+                        over: num - limit,
+                    })
+                })
+            })
+            .collect()
     }
 
     fn is_possible(&self, marbles: &MarbleConfig) -> bool {
-        self.obs.iter().all(|(num, color)| marbles.get(color) >= Some(num))
+        self.explain(marbles).is_empty()
+    }
+
+    // The minimum number of cubes of each color to add to the bag to make this game possible
+    fn additions_needed(&self, marbles: &MarbleConfig) -> MarbleConfig {
+        let mut additions = MarbleConfig::new();
+        for v in self.explain(marbles) {
+            let e = additions.entry(v.color).or_insert(0);
+            *e = (*e).max(v.over);
+        }
+        additions
+    }
+
+    // The smallest bag that makes this game possible
+    fn min_bag(&self) -> Counts {
+        let mut bag = [0; 3];
+        for (num, color) in self.obs() {
+            bag[color.index()] = bag[color.index()].max(*num);
+        }
+        bag
     }
 
     fn to_power(&self) -> u32 {
-        Color::values().iter().map(|color| {
-            let max = self.obs.iter().filter_map(|(num, c)| if c == color { Some(num) } else { None }).max().unwrap_or(&0);
-            max
-        }).product()
+        self.min_bag().iter().product()
+    }
+}
 
+// The Pareto frontier of bags that make at least k games possible using at
+// most budget cubes: every returned bag is feasible, and no other feasible bag
+// has fewer or equal cubes of every color.
+fn minimal_bags(games: &[GameInfo], k: usize, budget: u32) -> Vec<MarbleConfig> {
+    let mins = games.iter().map(|g| g.min_bag()).collect::<Vec<_>>();
+    if k > mins.len() {
+        return vec![];
+    }
+
+    // Each color of a minimal bag is either 0 or some game's requirement
+    let candidates = |i: usize| {
+        let mut v = mins.iter().map(|m| m[i]).chain(std::iter::once(0)).collect::<Vec<_>>();
+        v.sort();
+        v.dedup();
+        v
+    };
+
+    let mut bags: Vec<Counts> = vec![];
+    for red in candidates(0) {
+        for green in candidates(1) {
+            let mut blues = mins
+                .iter()
+                .filter(|m| m[0] <= red && m[1] <= green)
+                .map(|m| m[2])
+                .collect::<Vec<_>>();
+            if blues.len() < k {
+                continue;
+            }
+            blues.sort();
+            let blue = if k == 0 { 0 } else { blues[k - 1] };
+            if red + green + blue <= budget {
+                bags.push([red, green, blue]);
+            }
+        }
+    }
+
+    let dominates = |a: &Counts, b: &Counts| a != b && a.iter().zip(b).all(|(x, y)| x <= y);
+    bags.iter()
+        .filter(|b| !bags.iter().any(|other| dominates(other, b)))
+        .map(counts_to_config)
+        .collect()
+}
+
+struct BagEstimate {
+    bag: MarbleConfig,
+    log_likelihood: f64,
+    // The estimate ran into max_total while the likelihood was still rising,
+    // so a bigger bag may be more likely
+    at_limit: bool,
+    // Share of each color among all cubes drawn, which is where the bag's
+    // proportions go as it grows without bound
+    proportions: [f64; 3],
+}
+
+// Maximum-likelihood estimate of the bag contents, treating every draw of every
+// game as a sample without replacement (multivariate hypergeometric) from the
+// same bag, with the cubes put back between draws.  Bags are searched by
+// coordinate ascent up to max_total cubes per color, or the most drawn at
+// once if that's more.
+//
+// Bigger bags make draws look more like independent samples, so the
+// likelihood can keep rising well past any bound we search.  Then the
+// estimate ends at the search bound and only the proportions mean much.
+fn estimate_bag(games: &[GameInfo], max_total: u32) -> BagEstimate {
+    let draws = games
+        .iter()
+        .flat_map(|g| g.draws.iter().map(draw_counts))
+        .collect::<Vec<_>>();
+
+    let mut floor = [0; 3];
+    for d in &draws {
+        for i in 0..3 {
+            floor[i] = floor[i].max(d[i]);
+        }
+    }
+    let ceiling = floor.map(|f| f.max(max_total));
+
+    let mut ln_fact = vec![0.0f64; ceiling.iter().sum::<u32>() as usize + 1];
+    for i in 1..ln_fact.len() {
+        ln_fact[i] = ln_fact[i - 1] + (i as f64).ln();
+    }
+    let ln_choose = |n: u32, k: u32| ln_fact[n as usize] - ln_fact[k as usize] - ln_fact[(n - k) as usize];
+
+    let log_likelihood = |bag: &Counts| -> f64 {
+        let total = bag.iter().sum::<u32>();
+        draws
+            .iter()
+            .map(|d| {
+                let n = d.iter().sum::<u32>();
+                (0..3).map(|i| ln_choose(bag[i], d[i])).sum::<f64>() - ln_choose(total, n)
+            })
+            .sum()
+    };
+
+
+    let mut bag = floor;
+    let mut best = log_likelihood(&bag);
+    loop {
+        let mut improved = false;
+        for i in 0..3 {
+            for v in floor[i]..=ceiling[i] {
+                let mut candidate = bag;
+                candidate[i] = v;
+                let ll = log_likelihood(&candidate);
+                if ll > best + 1e-9 {
+                    best = ll;
+                    bag = candidate;
+                    improved = true;
+                }
+            }
+        }
+        if !improved {
+            break;
+        }
+    }
+
+    let drawn = (0..3).map(|i| draws.iter().map(|d| d[i]).sum::<u32>()).collect::<Vec<_>>();
+    let all = drawn.iter().sum::<u32>().max(1) as f64;
+
+    BagEstimate {
+        bag: counts_to_config(&bag),
+        log_likelihood: best,
+        at_limit: (0..3).any(|i| bag[i] == ceiling[i] && ceiling[i] > floor[i]),
+        proportions: [0, 1, 2].map(|i| drawn[i] as f64 / all),
     }
 }
 
@@ -101,12 +308,65 @@ fn main() {
 
     let game_powers_sum = games.iter().map(|g| g.to_power()).sum::<u32>();
 
-    let possible_games = games.iter().filter(|g| g.is_possible(&MARBLES));
+    let possible_games = games.iter().filter(|g| g.is_possible(&MARBLES)).collect::<Vec<_>>();
 
-    let game_ids_sum = possible_games.map(|g| g.id).sum::<u32>();
+    let game_ids_sum = possible_games.iter().map(|g| g.id).sum::<u32>();
 
 
     println!("Sum of possible game Ids: {game_ids_sum}");
 
     println!("Sum of game powers: {game_powers_sum}");
+
+    for g in games.iter().filter(|g| !g.is_possible(&MARBLES)) {
+        let reasons = g.explain(&MARBLES).iter().map(|v| v.to_string()).collect::<Vec<_>>();
+        let additions = g.additions_needed(&MARBLES);
+        let additions = Color::values()
+            .iter()
+            .filter_map(|c| additions.get(c).map(|n| format!("{n} {c:?}")))
+            .collect::<Vec<_>>();
+        println!(
+            "Game {} is impossible: {}; needs {} more",
+            g.id,
+            reasons.join(", "),
+            additions.join(", ")
+        );
+    }
+
+    let format_bag = |bag: &MarbleConfig| {
+        Color::values()
+            .iter()
+            .map(|c| format!("{} {c:?}", bag[c]))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+
+    let budget = MARBLES.values().sum::<u32>();
+    let bags = minimal_bags(&games, possible_games.len(), budget);
+    println!(
+        "Minimal bags of at most {budget} cubes making at least {} games possible:",
+        possible_games.len()
+    );
+    for bag in &bags {
+        println!("  {}", format_bag(bag));
+    }
+
+    let estimate = estimate_bag(&games, 100);
+    if estimate.at_limit {
+        let proportions = Color::values()
+            .iter()
+            .map(|c| format!("{:.1}% {c:?}", 100.0 * estimate.proportions[c.index()]))
+            .collect::<Vec<_>>();
+        println!(
+            "Bag likelihood still rising at the search bound: {} (log-likelihood {:.2}); proportions tend to {}",
+            format_bag(&estimate.bag),
+            estimate.log_likelihood,
+            proportions.join(", ")
+        );
+    } else {
+        println!(
+            "Most likely bag: {} (log-likelihood {:.2})",
+            format_bag(&estimate.bag),
+            estimate.log_likelihood
+        );
+    }
 }
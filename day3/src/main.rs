@@ -15,16 +15,152 @@ struct Number {
     number: u32,
 }
 
-fn is_symbol(c: &char) -> bool {
-    if c.is_digit(10) {
-        false
-    } else {
-        *c != '.'
+// Which characters count as symbols
+#[derive(Debug, Clone)]
+enum Symbols {
+    // Any character that isn't a digit, '.' or one of these
+    AllExcept(Vec<char>),
+    // Only these characters
+    Only(Vec<char>),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum NeighborCount {
+    Exactly(usize),
+    AtLeast(usize),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Aggregate {
+    Product,
+    Sum,
+    Max,
+}
+
+// How parts and gears are recognized on a schematic
+#[derive(Debug, Clone)]
+struct Rules {
+    symbols: Symbols,
+    // Symbols that may form a gear
+    gear_symbols: Vec<char>,
+    neighbors: NeighborCount,
+    aggregate: Aggregate,
+}
+
+impl Default for Rules {
+    // The rules from the puzzle: every non-digit non-'.' is a symbol, and a
+    // gear is a '*' with exactly two numbers around it.
+    fn default() -> Rules {
+        Rules {
+            symbols: Symbols::AllExcept(vec![]),
+            gear_symbols: vec!['*'],
+            neighbors: NeighborCount::Exactly(2),
+            aggregate: Aggregate::Product,
+        }
+    }
+}
+
+// A gear symbol with more adjacent numbers than the rules allow
+#[derive(Debug)]
+struct GearViolation {
+    pos: (usize, usize),
+    symbol: char,
+    numbers: Vec<u32>,
+}
+
+impl std::fmt::Display for GearViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} at ({}, {}) has {} adjacent numbers: {:?}",
+            self.symbol,
+            self.pos.0,
+            self.pos.1,
+            self.numbers.len(),
+            self.numbers
+        )
+    }
+}
+
+impl Rules {
+    // Override the default rules from command line options:
+    //   --symbols=CHARS     only these characters are symbols
+    //   --not-symbols=CHARS these characters are not symbols
+    //   --gears=CHARS       symbols that may form gears
+    //   --neighbors=N[+]    exactly N (or at least N) adjacent numbers
+    //   --aggregate=product|sum|max
+    fn from_args<I: IntoIterator<Item = String>>(args: I) -> Result<Rules, String> {
+        let mut rules = Rules::default();
+        for arg in args {
+            let (key, value) = arg.split_once('=').ok_or(format!("Expected --option=value, got {arg}"))?;
+            match key {
+                "--symbols" => rules.symbols = Symbols::Only(value.chars().collect()),
+                "--not-symbols" => rules.symbols = Symbols::AllExcept(value.chars().collect()),
+                "--gears" => rules.gear_symbols = value.chars().collect(),
+                "--neighbors" => {
+                    let parse = |n: &str| n.parse::<usize>().map_err(|e| format!("Bad neighbor count {value}: {e}"));
+                    rules.neighbors = match value.strip_suffix('+') {
+                        Some(n) => NeighborCount::AtLeast(parse(n)?),
+                        None => NeighborCount::Exactly(parse(value)?),
+                    }
+                }
+                "--aggregate" => {
+                    rules.aggregate = match value {
+                        "product" => Aggregate::Product,
+                        "sum" => Aggregate::Sum,
+                        "max" => Aggregate::Max,
+                        _ => return Err(format!("Unknown aggregate {value}")),
+                    }
+                }
+                _ => return Err(format!("Unknown option {key}")),
+            }
+        }
+        Ok(rules)
+    }
+
+    fn is_symbol(&self, c: &char) -> bool {
+        if c.is_ascii_digit() || *c == '.' {
+            return false;
+        }
+        match &self.symbols {
+            Symbols::AllExcept(excluded) => !excluded.contains(c),
+            Symbols::Only(included) => included.contains(c),
+        }
+    }
+
+    fn is_gear_symbol(&self, c: &char) -> bool {
+        self.is_symbol(c) && self.gear_symbols.contains(c)
+    }
+
+    // The gear value at a gear symbol, None if it isn't a gear, or the
+    // violation if it has too many numbers around it
+    fn gear_value(&self, symbol: char, pos: (usize, usize), numbers: &[&Number]) -> Result<Option<u64>, GearViolation> {
+        let n = numbers.len();
+        let (enough, too_many) = match self.neighbors {
+            NeighborCount::Exactly(k) => (n == k, n > k),
+            NeighborCount::AtLeast(k) => (n >= k, false),
+        };
+        if too_many {
+            return Err(GearViolation {
+                pos,
+                symbol,
+                numbers: numbers.iter().map(|n| n.number).collect(),
+            });
+        }
+        if !enough || n == 0 {
+            return Ok(None);
+        }
+        let values = numbers.iter().map(|n| n.number as u64);
+        Ok(Some(match self.aggregate {
+            Aggregate::Product => values.product(),
+            Aggregate::Sum => values.sum(),
+            Aggregate::Max => values.max().unwrap(),
+        }))
     }
 }
 
 impl Number {
-    fn has_adjacent_symbol(&self, board: &Board) -> bool {
+    fn has_adjacent_symbol(&self, board: &Board, rules: &Rules) -> bool {
         // Where are all the adjacent positions? We can just check all points in
         // the rectangle (row-1, firstcol-1) .. (row+1, lastcol+1) because a
         // number is not a symbol.
@@ -32,43 +168,53 @@ impl Number {
         let firstcol = self.firstcol as isize;
         let lastcol = self.lastcol as isize;
 
-        println!(
-            "Checking for adjacent symbol to {} {} {}:",
-            row, firstcol, lastcol
-        );
-
         ((row - 1)..(row + 2)).any(|row| {
             ((firstcol - 1)..(lastcol + 2)).any(|col| {
-                //println!("Checking ({}, {})", row, col);
-                if row < 0 || row >= board.len() as isize {
-                    false
-                } else if col < 0 || col >= board[row as usize].len() as isize {
+                if row < 0 || row >= board.len() as isize || col < 0 || col >= board[row as usize].len() as isize {
                     false
                 } else {
-                    let symbol = board[row as usize][col as usize];
-                    println!(
-                        "Symbol at {} {}: {} {}",
-                        row,
-                        col,
-                        symbol,
-                        is_symbol(&symbol)
-                    );
-                    is_symbol(&symbol)
+                    rules.is_symbol(&board[row as usize][col as usize])
                 }
             })
         })
     }
 
     fn is_adjacent_to(&self, (row, col): (usize, usize)) -> bool {
-        let row_in = row as isize;
-        let col_in = col as isize;
+        self.row + 1 >= row && self.row <= row + 1 && self.firstcol <= col + 1 && self.lastcol + 1 >= col
+    }
+}
 
-        let row = self.row as isize;
-        let firstcol = self.firstcol as isize;
-        let lastcol = self.lastcol as isize;
+// Numbers bucketed by row, each row sorted by column, so that the numbers
+// around a cell can be found without scanning the whole board.
+struct NumberIndex<'a> {
+    rows: Vec<Vec<&'a Number>>,
+}
 
-        (row - 1..row + 2)
-            .any(|row| (firstcol - 1..lastcol + 2).any(|col| row == row_in && col == col_in))
+impl<'a> NumberIndex<'a> {
+    fn new<I: IntoIterator<Item = &'a Number>>(numbers: I) -> NumberIndex<'a> {
+        let mut rows: Vec<Vec<&Number>> = Vec::new();
+        for number in numbers {
+            if rows.len() <= number.row {
+                rows.resize_with(number.row + 1, Vec::new);
+            }
+            rows[number.row].push(number);
+        }
+        for row in rows.iter_mut() {
+            row.sort_by_key(|n| n.firstcol);
+        }
+        NumberIndex { rows }
+    }
+
+    // All numbers adjacent to (row, col)
+    fn adjacent_to(&self, (row, col): (usize, usize)) -> Vec<&'a Number> {
+        (row.saturating_sub(1)..row + 2)
+            .filter_map(|r| self.rows.get(r))
+            .flat_map(|numbers| {
+                // Numbers in a row don't overlap, so they are sorted by lastcol too
+                let start = numbers.partition_point(|n| n.lastcol + 1 < col);
+                numbers[start..].iter().take_while(move |n| n.firstcol <= col + 1).copied()
+            })
+            .collect()
     }
 }
 
@@ -82,21 +228,20 @@ fn find_numbers(board: &Board) -> Vec<Number> {
         // XXX: Bleh.  This is ugly.  If I don't pass the arguments explicitly, I have to make finish_number a mutable closure, and then there are borrow conflicts!
         let finish_number =
             |startcol: usize, current_number: &mut String, numbers: &mut Vec<Number>| {
-                if current_number.len() > 0 {
+                if !current_number.is_empty() {
                     let number = current_number.parse::<u32>().unwrap();
                     numbers.push(Number {
-                        row: row,
+                        row,
                         firstcol: startcol,
                         lastcol: startcol + current_number.len() - 1,
-                        number: number,
+                        number,
                     });
                     current_number.clear();
                 }
             };
 
         for (col, c) in line.iter().enumerate() {
-            if c.is_digit(10) {
-                let digit = c.to_digit(10).unwrap();
+            if c.is_ascii_digit() {
                 current_number.push(*c);
             } else {
                 finish_number(
@@ -117,31 +262,479 @@ fn find_numbers(board: &Board) -> Vec<Number> {
     numbers
 }
 
-fn find_stars(board: &Board) -> Vec<(usize, usize)> {
-    let mut stars = Vec::new();
+fn find_gear_symbols(board: &Board, rules: &Rules) -> Vec<(usize, usize)> {
+    let mut symbols = Vec::new();
 
     for (row, line) in board.iter().enumerate() {
         for (col, c) in line.iter().enumerate() {
-            if *c == '*' {
-                stars.push((row, col));
+            if rules.is_gear_symbol(c) {
+                symbols.push((row, col));
             }
         }
     }
 
-    stars
+    symbols
+}
+
+fn get_gear(board: &Board, rules: &Rules, numbers: &NumberIndex, (row, col): (usize, usize)) -> Result<Option<u64>, GearViolation> {
+    let adjacent_numbers = numbers.adjacent_to((row, col));
+    debug_assert!(adjacent_numbers.iter().all(|n| n.is_adjacent_to((row, col))));
+
+    rules.gear_value(board[row][col], (row, col), &adjacent_numbers)
+}
+
+// Part-number and gear-ratio sums over all of a board
+fn full_totals(board: &Board, rules: &Rules) -> (u64, u64) {
+    let numbers = find_numbers(board);
+    let parts = numbers
+        .iter()
+        .filter(|n| n.has_adjacent_symbol(board, rules))
+        .collect::<Vec<_>>();
+    let part_sum = parts.iter().map(|n| n.number as u64).sum();
+    let index = NumberIndex::new(parts);
+    let gear_sum = find_gear_symbols(board, rules)
+        .into_iter()
+        .filter_map(|pos| get_gear(board, rules, &index, pos).ok().flatten())
+        .sum();
+    (part_sum, gear_sum)
+}
+
+// A board whose numbers and sums are kept up to date one cell edit at a time
+struct Schematic {
+    board: Board,
+    rules: Rules,
+    // Numbers in each row, keyed by their first column
+    rows: Vec<std::collections::BTreeMap<usize, Number>>,
+    part_sum: u64,
+    gear_sum: u64,
+}
+
+impl Schematic {
+    fn new(board: Board, rules: Rules) -> Schematic {
+        let mut rows = board.iter().map(|_| std::collections::BTreeMap::new()).collect::<Vec<_>>();
+        for number in find_numbers(&board) {
+            rows[number.row].insert(number.firstcol, number);
+        }
+        let (part_sum, gear_sum) = full_totals(&board, &rules);
+        Schematic {
+            board,
+            rules,
+            rows,
+            part_sum,
+            gear_sum,
+        }
+    }
+
+    // Numbers adjacent to (row, col)
+    fn adjacent_to(&self, (row, col): (usize, usize)) -> Vec<&Number> {
+        (row.saturating_sub(1)..row + 2)
+            .filter_map(|r| self.rows.get(r))
+            .flat_map(|numbers| {
+                numbers
+                    .range(..=col + 1)
+                    .rev()
+                    .map(|(_, n)| n)
+                    .take_while(move |n| n.lastcol + 1 >= col)
+            })
+            .collect()
+    }
+
+    fn part_value(&self, number: &Number) -> u64 {
+        if number.has_adjacent_symbol(&self.board, &self.rules) {
+            number.number as u64
+        } else {
+            0
+        }
+    }
+
+    fn gear_value(&self, (row, col): (usize, usize)) -> u64 {
+        let c = self.board[row][col];
+        if !self.rules.is_gear_symbol(&c) {
+            return 0;
+        }
+        let numbers = self.adjacent_to((row, col));
+        self.rules.gear_value(c, (row, col), &numbers).ok().flatten().unwrap_or(0)
+    }
+
+    // Cells whose gear value may depend on the numbers adjacent to pos
+    fn gear_region(&self, (row, col): (usize, usize)) -> std::collections::HashSet<(usize, usize)> {
+        let mut region = std::collections::HashSet::new();
+        let mut add_rect = |rows: std::ops::RangeInclusive<usize>, cols: std::ops::RangeInclusive<usize>| {
+            for r in rows {
+                for c in cols.clone() {
+                    if r < self.board.len() && c < self.board[r].len() {
+                        region.insert((r, c));
+                    }
+                }
+            }
+        };
+        add_rect(row.saturating_sub(1)..=row + 1, col.saturating_sub(1)..=col + 1);
+        for n in self.adjacent_to((row, col)) {
+            add_rect(n.row.saturating_sub(1)..=n.row + 1, n.firstcol.saturating_sub(1)..=n.lastcol + 1);
+        }
+        region
+    }
+
+    // Set a single cell to a digit, a symbol or '.', updating the numbers and
+    // both sums from the neighborhood of the cell only.
+    fn set(&mut self, (row, col): (usize, usize), c: char) {
+        let before_region = self.gear_region((row, col));
+
+        // Take away everything the old neighborhood contributed
+        self.part_sum -= self.adjacent_to((row, col)).iter().map(|n| self.part_value(n)).sum::<u64>();
+        self.gear_sum -= before_region.iter().map(|pos| self.gear_value(*pos)).sum::<u64>();
+
+        // Numbers on this row touching the cell may merge or split, so drop them
+        // and rescan the span they covered
+        let touching = self.rows[row]
+            .range(..=col + 1)
+            .rev()
+            .take_while(|(_, n)| n.lastcol + 1 >= col)
+            .map(|(first, n)| (*first, n.lastcol))
+            .collect::<Vec<_>>();
+        let first = touching.iter().map(|(f, _)| *f).chain(std::iter::once(col)).min().unwrap();
+        let last = touching.iter().map(|(_, l)| *l).chain(std::iter::once(col)).max().unwrap();
+        for (f, _) in touching {
+            self.rows[row].remove(&f);
+        }
+
+        self.board[row][col] = c;
+
+        let segment = vec![self.board[row][first..=last].to_vec()];
+        for mut number in find_numbers(&segment) {
+            number.row = row;
+            number.firstcol += first;
+            number.lastcol += first;
+            self.rows[row].insert(number.firstcol, number);
+        }
+
+        // Add back what the new neighborhood contributes
+        let mut after_region = self.gear_region((row, col));
+        after_region.extend(before_region);
+        self.part_sum += self.adjacent_to((row, col)).iter().map(|n| self.part_value(n)).sum::<u64>();
+        self.gear_sum += after_region.iter().map(|pos| self.gear_value(*pos)).sum::<u64>();
+    }
+
+    // Compare the incremental state against a full rescan of the board
+    fn check(&self) -> Result<(), String> {
+        let numbers = find_numbers(&self.board);
+        let ours = self.rows.iter().flat_map(|r| r.values()).collect::<Vec<_>>();
+        let same_numbers = numbers.len() == ours.len()
+            && numbers.iter().zip(&ours).all(|(a, b)| {
+                (a.row, a.firstcol, a.lastcol, a.number) == (b.row, b.firstcol, b.lastcol, b.number)
+            });
+        if !same_numbers {
+            return Err("numbers differ from a full rescan".to_string());
+        }
+        let totals = full_totals(&self.board, &self.rules);
+        if totals != (self.part_sum, self.gear_sum) {
+            return Err(format!(
+                "sums ({}, {}) differ from a full rescan {:?}",
+                self.part_sum, self.gear_sum, totals
+            ));
+        }
+        Ok(())
+    }
 }
 
-fn get_gear(numbers: &Vec<&Number>, (row, col): (usize, usize)) -> Option<u32> {
-    let adjacent_numbers: Vec<_> = numbers
+// What the solver decided about each cell, for rendering
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Mark {
+    Plain,
+    // A digit of a number next to a symbol
+    Part,
+    // A digit of a number with no symbol around it
+    Ignored,
+    Symbol,
+    // A gear symbol that forms a valid gear
+    Gear,
+    // A digit of a number that takes part in a valid gear
+    Partner,
+    // A gear symbol with too many numbers around it
+    Violation,
+}
+
+impl Mark {
+    fn ansi(&self) -> &'static str {
+        match self {
+            Mark::Plain => "\x1b[0;2m",
+            Mark::Part => "\x1b[0;32m",
+            Mark::Ignored => "\x1b[0;90m",
+            Mark::Symbol => "\x1b[0;33m",
+            Mark::Gear => "\x1b[0;1;35m",
+            Mark::Partner => "\x1b[0;1;36m",
+            Mark::Violation => "\x1b[0;1;41m",
+        }
+    }
+
+    fn css_class(&self) -> &'static str {
+        match self {
+            Mark::Plain => "plain",
+            Mark::Part => "part",
+            Mark::Ignored => "ignored",
+            Mark::Symbol => "symbol",
+            Mark::Gear => "gear",
+            Mark::Partner => "partner",
+            Mark::Violation => "violation",
+        }
+    }
+}
+
+fn annotate(board: &Board, rules: &Rules, numbers: &[Number]) -> Vec<Vec<Mark>> {
+    let mut marks = board
         .iter()
-        .filter(|number| number.is_adjacent_to((row, col)))
-        .collect();
+        .map(|line| {
+            line.iter()
+                .map(|c| if rules.is_symbol(c) { Mark::Symbol } else { Mark::Plain })
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    let mut parts = vec![];
+    for number in numbers {
+        let mark = if number.has_adjacent_symbol(board, rules) {
+            parts.push(number);
+            Mark::Part
+        } else {
+            Mark::Ignored
+        };
+        marks[number.row][number.firstcol..=number.lastcol].fill(mark);
+    }
+
+    let index = NumberIndex::new(parts);
+    for (row, col) in find_gear_symbols(board, rules) {
+        match get_gear(board, rules, &index, (row, col)) {
+            Ok(Some(_)) => {
+                marks[row][col] = Mark::Gear;
+                for n in index.adjacent_to((row, col)) {
+                    marks[n.row][n.firstcol..=n.lastcol].fill(Mark::Partner);
+                }
+            }
+            Ok(None) => (),
+            Err(_) => marks[row][col] = Mark::Violation,
+        }
+    }
 
-    match adjacent_numbers.as_slice() {
-        [n1, n2] => Some(n1.number * n2.number),
-        [n] => None,
-        [] => None,
-        numbers => panic!("({}, {}) has more than 2 adjacent numbers: {:?}", row, col, numbers)
+    marks
+}
+
+fn render_ansi(board: &Board, marks: &[Vec<Mark>]) -> String {
+    let mut out = String::new();
+    for (line, marks) in board.iter().zip(marks) {
+        let mut current = None;
+        for (c, mark) in line.iter().zip(marks) {
+            if current != Some(mark) {
+                out.push_str(mark.ansi());
+                current = Some(mark);
+            }
+            out.push(*c);
+        }
+        out.push_str("\x1b[0m\n");
+    }
+    out
+}
+
+fn render_html(board: &Board, marks: &[Vec<Mark>]) -> String {
+    let mut out = String::from(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>Schematic</title>
+<style>
+body { background: #111; color: #ddd; }
+pre { font-family: monospace; line-height: 1.1; }
+.plain { color: #555; }
+.part { color: #4c4; }
+.ignored { color: #888; text-decoration: line-through; }
+.symbol { color: #dd4; }
+.gear { color: #f4f; font-weight: bold; }
+.partner { color: #4ff; font-weight: bold; }
+.violation { background: #c22; color: #fff; font-weight: bold; }
+</style>
+</head>
+<body>
+<p>
+<span class="part">counted part</span>
+<span class="ignored">ignored number</span>
+<span class="symbol">symbol</span>
+<span class="gear">gear</span>
+<span class="partner">gear partner</span>
+<span class="violation">over-connected gear</span>
+</p>
+<pre>
+"#,
+    );
+    for (line, marks) in board.iter().zip(marks) {
+        let mut current: Option<Mark> = None;
+        for (c, mark) in line.iter().zip(marks) {
+            if current != Some(*mark) {
+                if current.is_some() {
+                    out.push_str("</span>");
+                }
+                out.push_str(&format!("<span class=\"{}\">", mark.css_class()));
+                current = Some(*mark);
+            }
+            match c {
+                '<' => out.push_str("&lt;"),
+                '>' => out.push_str("&gt;"),
+                '&' => out.push_str("&amp;"),
+                c => out.push(*c),
+            }
+        }
+        if current.is_some() {
+            out.push_str("</span>");
+        }
+        out.push('\n');
+    }
+    out.push_str("</pre>\n</body>\n</html>\n");
+    out
+}
+
+// Bipartite graph between numbers and the symbols they touch
+struct PartGraph<'a> {
+    numbers: &'a [Number],
+    symbols: Vec<(usize, usize)>,
+    // For each symbol, the indices of the numbers around it
+    symbol_numbers: Vec<Vec<usize>>,
+    // For each number, the indices of the symbols around it
+    number_symbols: Vec<Vec<usize>>,
+    components: Vec<Component>,
+    // Index into components for each symbol, and for each number that
+    // touches a symbol
+    symbol_component: Vec<usize>,
+    number_component: Vec<Option<usize>>,
+}
+
+// A connected group of numbers and symbols, i.e. a subassembly
+#[derive(Debug)]
+struct Component {
+    numbers: Vec<usize>,
+    symbols: Vec<usize>,
+    total: u32,
+}
+
+impl<'a> PartGraph<'a> {
+    fn new(board: &Board, rules: &Rules, numbers: &'a [Number]) -> PartGraph<'a> {
+        let ids = numbers
+            .iter()
+            .enumerate()
+            .map(|(i, n)| ((n.row, n.firstcol), i))
+            .collect::<std::collections::HashMap<_, _>>();
+        let index = NumberIndex::new(numbers);
+
+        let symbols = board
+            .iter()
+            .enumerate()
+            .flat_map(|(row, line)| {
+                line.iter()
+                    .enumerate()
+                    .filter(|(_, c)| rules.is_symbol(c))
+                    .map(move |(col, _)| (row, col))
+            })
+            .collect::<Vec<_>>();
+
+        let mut number_symbols = vec![vec![]; numbers.len()];
+        let symbol_numbers = symbols
+            .iter()
+            .enumerate()
+            .map(|(s, pos)| {
+                index
+                    .adjacent_to(*pos)
+                    .iter()
+                    .map(|n| {
+                        let i = ids[&(n.row, n.firstcol)];
+                        number_symbols[i].push(s);
+                        i
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let mut graph = PartGraph {
+            numbers,
+            symbols,
+            symbol_numbers,
+            number_symbols,
+            components: vec![],
+            symbol_component: vec![],
+            number_component: vec![],
+        };
+        graph.find_components();
+        graph
+    }
+
+    // Connected components that contain at least one symbol.  Isolated
+    // numbers are not part of any component.
+    fn find_components(&mut self) {
+        let mut seen = vec![false; self.symbols.len()];
+        self.symbol_component = vec![usize::MAX; self.symbols.len()];
+        self.number_component = vec![None; self.numbers.len()];
+
+        for start in 0..self.symbols.len() {
+            if seen[start] {
+                continue;
+            }
+            seen[start] = true;
+            let id = self.components.len();
+            let mut symbols = vec![];
+            let mut numbers = std::collections::BTreeSet::new();
+            let mut stack = vec![start];
+            while let Some(s) = stack.pop() {
+                symbols.push(s);
+                self.symbol_component[s] = id;
+                for &n in &self.symbol_numbers[s] {
+                    if numbers.insert(n) {
+                        self.number_component[n] = Some(id);
+                        for &other in &self.number_symbols[n] {
+                            if !seen[other] {
+                                seen[other] = true;
+                                stack.push(other);
+                            }
+                        }
+                    }
+                }
+            }
+            symbols.sort();
+            let numbers = numbers.into_iter().collect::<Vec<_>>();
+            let total = numbers.iter().map(|&n| self.numbers[n].number).sum();
+            self.components.push(Component { numbers, symbols, total });
+        }
+    }
+
+    fn components(&self) -> &[Component] {
+        &self.components
+    }
+
+    // The component containing the given number, if it touches any symbol
+    fn component_of(&self, number: usize) -> Option<&Component> {
+        self.number_component[number].map(|c| &self.components[c])
+    }
+
+    // The component containing the symbol at pos, if there's a symbol there
+    fn component_at(&self, pos: (usize, usize)) -> Option<&Component> {
+        let symbol = self.symbols.binary_search(&pos).ok()?;
+        Some(&self.components[self.symbol_component[symbol]])
+    }
+
+    // Numbers adjacent to more than one symbol
+    fn shared_numbers(&self) -> Vec<&'a Number> {
+        self.numbers
+            .iter()
+            .zip(&self.number_symbols)
+            .filter(|(_, s)| s.len() > 1)
+            .map(|(n, _)| n)
+            .collect()
+    }
+
+    // Numbers not adjacent to any symbol
+    fn isolated_numbers(&self) -> Vec<&'a Number> {
+        self.numbers
+            .iter()
+            .zip(&self.number_symbols)
+            .filter(|(_, s)| s.is_empty())
+            .map(|(n, _)| n)
+            .collect()
     }
 }
 
@@ -150,11 +743,21 @@ fn main() {
 
     let board = get_board();
 
-    let numbers = find_numbers(&board);
+    // --ansi prints the annotated schematic, --html=FILE writes it as HTML,
+    // --edit=ROW,COL,CHAR applies an edit afterwards; everything else
+    // configures the rules
+    let (other_args, rule_args): (Vec<_>, Vec<_>) = std::env::args()
+        .skip(1)
+        .partition(|a| a == "--ansi" || a.starts_with("--html=") || a.starts_with("--edit="));
+    let (edit_args, render_args): (Vec<_>, Vec<_>) = other_args.into_iter().partition(|a| a.starts_with("--edit="));
+
+    let rules = Rules::from_args(rule_args).unwrap_or_else(|e| panic!("{e}"));
 
-    let numbers_it = numbers
+    let all_numbers = find_numbers(&board);
+
+    let numbers_it = all_numbers
         .iter()
-        .filter(|number| number.has_adjacent_symbol(&board));
+        .filter(|number| number.has_adjacent_symbol(&board, &rules));
 
         //.collect::<Vec<_>>();
 
@@ -167,13 +770,80 @@ fn main() {
 
     println!("Number sum: {}", number_sum);
 
-    let stars = find_stars(&board);
+    let gear_symbols = find_gear_symbols(&board, &rules);
 
-    //println!("Stars: {:?}", stars);
+    let numbers = NumberIndex::new(numbers);
 
-    //let starred_gear: Vec<Option<u32>> = stars.iter().map(|star| get_gear(&numbers, *star)).collect();
+    let (gears, violations): (Vec<_>, Vec<_>) = gear_symbols
+        .iter()
+        .map(|pos| get_gear(&board, &rules, &numbers, *pos))
+        .partition(|r| r.is_ok());
 
-    //println!("Starred gear: {:?}", starred_gear);
+    for v in violations {
+        println!("Violation: {}", v.unwrap_err());
+    }
+
+    println!("Starred gear sum: {}", gears.into_iter().filter_map(|g| g.unwrap()).sum::<u64>());
+
+    let graph = PartGraph::new(&board, &rules, &all_numbers);
+    let components = graph.components();
+    debug_assert_eq!(components.iter().map(|c| c.total).sum::<u32>(), number_sum);
+
+    println!(
+        "{} components, {} shared numbers, {} isolated numbers",
+        components.len(),
+        graph.shared_numbers().len(),
+        graph.isolated_numbers().len()
+    );
+    if let Some(largest) = components.iter().max_by_key(|c| c.total) {
+        println!(
+            "Largest component: {} numbers, {} symbols, total {}",
+            largest.numbers.len(),
+            largest.symbols.len(),
+            largest.total
+        );
+    }
+    if let Some(component) = all_numbers.first().and_then(|_| graph.component_of(0)) {
+        println!(
+            "First number {} belongs to a subassembly totalling {}",
+            all_numbers[0].number, component.total
+        );
+    }
+    if let Some((component, pos)) = graph.symbols.first().and_then(|pos| Some((graph.component_at(*pos)?, pos))) {
+        println!(
+            "First symbol at {pos:?} belongs to a subassembly of {} numbers",
+            component.numbers.len()
+        );
+    }
+
+    if !render_args.is_empty() {
+        let marks = annotate(&board, &rules, &all_numbers);
+        for arg in render_args {
+            match arg.strip_prefix("--html=") {
+                Some(path) => std::fs::write(path, render_html(&board, &marks)).expect("Unable to write HTML"),
+                None => print!("{}", render_ansi(&board, &marks)),
+            }
+        }
+    }
 
-    println!("Starred gear sum: {}", stars.iter().filter_map(|star| get_gear(&numbers, *star)).sum::<u32>());
+    if !edit_args.is_empty() {
+        let mut schematic = Schematic::new(board, rules);
+        for arg in edit_args {
+            let edit = arg.strip_prefix("--edit=").unwrap();
+            let (row, col, c) = match edit.splitn(3, ',').collect::<Vec<_>>().as_slice() {
+                [row, col, c] if c.chars().count() == 1 => (
+                    row.parse::<usize>().expect("Expected row"),
+                    col.parse::<usize>().expect("Expected column"),
+                    c.chars().next().unwrap(),
+                ),
+                _ => panic!("Expected --edit=ROW,COL,CHAR, got {arg}"),
+            };
+            schematic.set((row, col), c);
+            schematic.check().unwrap_or_else(|e| panic!("After {edit}: {e}"));
+            println!(
+                "After setting ({row}, {col}) to {c}: number sum {}, gear sum {}",
+                schematic.part_sum, schematic.gear_sum
+            );
+        }
+    }
 }
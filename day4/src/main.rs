@@ -1,25 +1,17 @@
 use std::collections::HashSet;
-use std::collections::VecDeque;
 
 #[derive(Debug)]
 struct Card {
     id: u32,
-    win: Vec<u32>,
-    mine: Vec<u32>
+    // Built once at parse time so matches() doesn't rebuild them
+    win: HashSet<u32>,
+    mine: HashSet<u32>,
 }
 
 impl Card {
 
     fn matches(&self) -> u32 {
-        let win: HashSet<_> = self.win.iter().collect();
-        let mine: HashSet<_> = self.mine.iter().collect();
-        let num_winning = win.intersection(&mine).count() as u32;
-        num_winning
-    }
-
-    fn score(&self) -> u32 {
-        let num_winning = self.matches();
-        if num_winning == 0 { 0 } else { 2_u32.pow(num_winning - 1) }
+        self.mine.iter().filter(|n| self.win.contains(n)).count() as u32
     }
 }
 
@@ -28,9 +20,9 @@ impl Card {
 fn line_to_card (str: &str) -> Card {
     // Hack off Card n
     let split = str.splitn(2, ": ").collect::<Vec<&str>>();
-    let id = split.iter().nth(0).unwrap().split_ascii_whitespace().nth(1).unwrap().parse::<u32>().unwrap();
+    let id = split.first().unwrap().split_ascii_whitespace().nth(1).unwrap().parse::<u32>().unwrap();
     
-    let str = split.iter().nth(1).unwrap();
+    let str = split.get(1).unwrap();
     
     // :-(  If I call .as_slice(), the split is a temporary and is freed right away.
     let split = str.splitn(2, '|').collect::<Vec<&str>>();
@@ -39,45 +31,219 @@ fn line_to_card (str: &str) -> Card {
         [a,b] => (*a,*b),
         _ => panic!("Invalid card")
     };
-    let win = first.split_whitespace().map(|x| x.parse::<u32>().unwrap()).collect::<Vec<u32>>();
-    let mine = second.split_whitespace().map(|x| x.parse::<u32>().unwrap()).collect::<Vec<u32>>();
+    let win = first.split_whitespace().map(|x| x.parse::<u32>().unwrap()).collect::<HashSet<u32>>();
+    let mine = second.split_whitespace().map(|x| x.parse::<u32>().unwrap()).collect::<HashSet<u32>>();
     Card{id, win, mine}
 }
 
-fn main() {
-    let cards = std::io::stdin().lines().map(|l| line_to_card(&l.unwrap())).collect::<Vec<Card>>();
+#[derive(Debug, Clone, Copy)]
+enum Points {
+    // 1 point for the first match, doubled for each one after that
+    Doubling,
+    // This many points per match
+    Linear(u64),
+}
 
-    //dbg!(&cards);
+#[derive(Debug, Clone, Copy)]
+enum WindowLength {
+    // One card per match
+    Matches,
+    Fixed(usize),
+}
 
-    println!("Card score sum: {}", cards.iter().map(|c| c.score()).sum::<u32>());
+// What happens to copies won past the last card
+#[derive(Debug, Clone, Copy)]
+enum PastEnd {
+    Drop,
+    Wrap,
+}
 
-    // part 2...
-    let mut queue = cards.iter().collect::<VecDeque<&Card>>();
-    let mut fcards = Vec::from(queue.clone());
+#[derive(Debug, Clone)]
+struct Rules {
+    points: Points,
+    // The most cards a single card can win
+    max_won: Option<usize>,
+    // How many cards after the winning card the won cards start
+    offset: usize,
+    length: WindowLength,
+    past_end: PastEnd,
+}
+
+impl Default for Rules {
+    // The puzzle rules: doubling points, and copies of the next N cards
+    fn default() -> Rules {
+        Rules {
+            points: Points::Doubling,
+            max_won: None,
+            offset: 1,
+            length: WindowLength::Matches,
+            past_end: PastEnd::Drop,
+        }
+    }
+}
+
+impl Rules {
+    // Override the default rules from command line options:
+    //   --points=double|linear:K
+    //   --max-won=N
+    //   --offset=N
+    //   --length=matches|N
+    //   --past-end=drop|wrap
+    fn from_args<I: IntoIterator<Item = String>>(args: I) -> Result<Rules, String> {
+        let mut rules = Rules::default();
+        let number = |key: &str, value: &str| value.parse::<usize>().map_err(|e| format!("Bad {key} {value}: {e}"));
+        for arg in args {
+            let (key, value) = arg.split_once('=').ok_or(format!("Expected --option=value, got {arg}"))?;
+            match key {
+                "--points" => {
+                    rules.points = match value.split_once(':') {
+                        None if value == "double" => Points::Doubling,
+                        Some(("linear", k)) => Points::Linear(number(key, k)? as u64),
+                        _ => return Err(format!("Unknown points formula {value}")),
+                    }
+                }
+                "--max-won" => rules.max_won = Some(number(key, value)?),
+                "--offset" => rules.offset = number(key, value)?,
+                "--length" => {
+                    rules.length = match value {
+                        "matches" => WindowLength::Matches,
+                        n => WindowLength::Fixed(number(key, n)?),
+                    }
+                }
+                "--past-end" => {
+                    rules.past_end = match value {
+                        "drop" => PastEnd::Drop,
+                        "wrap" => PastEnd::Wrap,
+                        _ => return Err(format!("Unknown past-end rule {value}")),
+                    }
+                }
+                _ => return Err(format!("Unknown option {key}")),
+            }
+        }
+        Ok(rules)
+    }
+
+    fn points(&self, matches: u32) -> u64 {
+        match self.points {
+            Points::Doubling if matches == 0 => 0,
+            Points::Doubling => 2_u64.pow(matches - 1),
+            Points::Linear(k) => k * matches as u64,
+        }
+    }
+
+    // How many cards a card with this many matches wins
+    fn cards_won(&self, matches: u32) -> usize {
+        if matches == 0 {
+            return 0;
+        }
+        let won = match self.length {
+            WindowLength::Matches => matches as usize,
+            WindowLength::Fixed(n) => n,
+        };
+        self.max_won.map_or(won, |max| won.min(max))
+    }
 
-    while !queue.is_empty() {
-        let card = queue.pop_front().unwrap();
-        let matches = card.matches();
+    // Positions of the cards won by the card at position i
+    fn window(&self, i: usize, matches: u32, num_cards: usize) -> impl Iterator<Item = usize> + '_ {
+        let start = i + self.offset;
+        (start..start + self.cards_won(matches)).filter_map(move |j| match self.past_end {
+            PastEnd::Drop => (j < num_cards).then_some(j),
+            PastEnd::Wrap => Some(j % num_cards),
+        })
+    }
+}
 
-        //dbg!(card);
+// How many copies of each card we end up with.  Each card adds its own count
+// to every card it wins.  When copies only go forward we do that with a
+// running difference array, so the whole thing is linear in the number of
+// cards; otherwise we walk the cards in topological order, and a cycle means
+// the copies never stop.
+fn copy_counts(cards: &[Card], rules: &Rules) -> Result<Vec<u128>, String> {
+    for (i, card) in cards.iter().enumerate() {
+        if card.id as usize != i + 1 {
+            return Err(format!("Card {} is out of order", card.id));
+        }
+    }
 
-        // OK, we get copies of cards [card.id+1 .. card.id+matches]
-        //dbg!("Adding cards to queue", card, matches);
+    if matches!(rules.past_end, PastEnd::Drop) && rules.offset > 0 {
+        let mut counts = vec![0u128; cards.len()];
+        // diff[i] is the change in pending copies when we reach card i
+        let mut diff = vec![0i128; cards.len() + 1];
+        let mut pending = 0i128;
+
+        for (i, card) in cards.iter().enumerate() {
+            pending += diff[i];
+            counts[i] = 1 + pending as u128;
+
+            // Won copies of cards past the end don't exist
+            let start = (i + rules.offset).min(cards.len());
+            let end = (start + rules.cards_won(card.matches())).min(cards.len());
+            let count = i128::try_from(counts[i]).map_err(|_| "Too many copies".to_string())?;
+            diff[start] += count;
+            diff[end] -= count;
+        }
+
+        return Ok(counts);
+    }
 
-        // I guess a better way to solve this would be to use memoization!  Oh well.
+    let wins = cards
+        .iter()
+        .enumerate()
+        .map(|(i, card)| rules.window(i, card.matches(), cards.len()).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    let mut indegree = vec![0usize; cards.len()];
+    for &j in wins.iter().flatten() {
+        indegree[j] += 1;
+    }
 
-        ((card.id+1)..=(card.id+matches)).for_each(|id| {
-            //println!("Adding copy of {id}");
-            let card = cards.iter().find(|c| c.id == id).unwrap();
-            //dbg!("Adding card", card);
-            queue.push_back(card);
-            fcards.push(card);
-            
-        });
+    let mut counts = vec![1u128; cards.len()];
+    let mut ready = (0..cards.len()).filter(|&i| indegree[i] == 0).collect::<Vec<_>>();
+    let mut done = 0;
+    while let Some(i) = ready.pop() {
+        done += 1;
+        for &j in &wins[i] {
+            counts[j] = counts[j].checked_add(counts[i]).ok_or("Too many copies".to_string())?;
+            indegree[j] -= 1;
+            if indegree[j] == 0 {
+                ready.push(j);
+            }
+        }
+    }
 
+    if done < cards.len() {
+        let stuck = (0..cards.len())
+            .filter(|&i| indegree[i] > 0)
+            .map(|i| cards[i].id.to_string())
+            .collect::<Vec<_>>();
+        return Err(format!("Cards get copies forever: {}", stuck.join(", ")));
     }
 
-    println!("Final: {}", fcards.len());
+    Ok(counts)
+}
+
+fn main() {
+    // --report prints a per-card CSV report; everything else configures the rules
+    let (report_args, rule_args): (Vec<_>, Vec<_>) = std::env::args().skip(1).partition(|a| a == "--report");
+    let rules = Rules::from_args(rule_args).unwrap_or_else(|e| panic!("{e}"));
+
+    let cards = std::io::stdin().lines().map(|l| line_to_card(&l.unwrap())).collect::<Vec<Card>>();
+
+    //dbg!(&cards);
 
+    println!("Card score sum: {}", cards.iter().map(|c| rules.points(c.matches())).sum::<u64>());
 
+    // part 2...
+    let counts = copy_counts(&cards, &rules);
+    match &counts {
+        Ok(counts) => println!("Final: {}", counts.iter().sum::<u128>()),
+        Err(e) => println!("No final count: {e}"),
+    }
+
+    if !report_args.is_empty() {
+        println!("card,matches,points,copies");
+        for (i, card) in cards.iter().enumerate() {
+            let copies = counts.as_ref().map_or("inf".to_string(), |c| c[i].to_string());
+            println!("{},{},{},{}", card.id, card.matches(), rules.points(card.matches()), copies);
+        }
+    }
 }
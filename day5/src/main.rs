@@ -9,8 +9,97 @@ struct OneRangeMap {
 
 #[derive(Debug)]
 struct MapRangeResult {
-    mapped: Vec<Range<usize>>,
-    unmapped: Vec<Range<usize>>,
+    mapped: IntervalSet,
+    unmapped: IntervalSet,
+}
+
+// A set of ids as sorted, merged, non-empty ranges
+#[derive(Debug, Clone, Default, PartialEq)]
+struct IntervalSet {
+    ranges: Vec<Range<usize>>,
+}
+
+impl IntervalSet {
+    fn from_ranges<I: IntoIterator<Item = Range<usize>>>(ranges: I) -> IntervalSet {
+        let mut sorted = ranges.into_iter().filter(|r| !r.is_empty()).collect::<Vec<_>>();
+        sorted.sort_by_key(|r| r.start);
+
+        let mut ranges: Vec<Range<usize>> = vec![];
+        for r in sorted {
+            match ranges.last_mut() {
+                // Overlapping or adjacent
+                Some(last) if r.start <= last.end => last.end = last.end.max(r.end),
+                _ => ranges.push(r),
+            }
+        }
+        IntervalSet { ranges }
+    }
+
+    fn from_range(range: Range<usize>) -> IntervalSet {
+        IntervalSet::from_ranges(std::iter::once(range))
+    }
+
+    // Number of ranges, not number of ids
+    fn len(&self) -> usize {
+        self.ranges.len()
+    }
+
+    fn min(&self) -> Option<usize> {
+        self.ranges.first().map(|r| r.start)
+    }
+
+    fn union(&self, other: &IntervalSet) -> IntervalSet {
+        IntervalSet::from_ranges(self.ranges.iter().chain(&other.ranges).cloned())
+    }
+
+    fn intersection(&self, other: &IntervalSet) -> IntervalSet {
+        let mut ranges = vec![];
+        let (mut i, mut j) = (0, 0);
+        while i < self.ranges.len() && j < other.ranges.len() {
+            let (a, b) = (&self.ranges[i], &other.ranges[j]);
+            if let Some(r) = range_intersect(a, b) {
+                ranges.push(r);
+            }
+            if a.end < b.end {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+        IntervalSet { ranges }
+    }
+
+    fn difference(&self, other: &IntervalSet) -> IntervalSet {
+        let mut ranges = vec![];
+        let mut j = 0;
+        for r in &self.ranges {
+            let mut start = r.start;
+            // Skip the ranges of other that end before this one starts
+            while j < other.ranges.len() && other.ranges[j].end <= start {
+                j += 1;
+            }
+            let mut k = j;
+            while k < other.ranges.len() && other.ranges[k].start < r.end {
+                let cut = &other.ranges[k];
+                if cut.start > start {
+                    ranges.push(start..cut.start);
+                }
+                start = start.max(cut.end);
+                k += 1;
+            }
+            if start < r.end {
+                ranges.push(start..r.end);
+            }
+        }
+        IntervalSet { ranges }
+    }
+
+    fn shift(&self, offset: isize) -> IntervalSet {
+        let shift = |x: usize| x.checked_add_signed(offset).expect("Shifted out of range");
+        IntervalSet {
+            ranges: self.ranges.iter().map(|r| shift(r.start)..shift(r.end)).collect(),
+        }
+    }
 }
 
 fn range_intersect(a: &Range<usize>, b: &Range<usize>) -> Option<Range<usize>> {
@@ -28,73 +117,100 @@ impl OneRangeMap {
 
         let idx = id - self.src_range.start;
 
-        return Some(self.dest_range.start + idx);
+        Some(self.dest_range.start + idx)
+    }
+
+    fn map_range(&self, in_ranges: &IntervalSet) -> MapRangeResult {
+        let src = IntervalSet::from_range(self.src_range.clone());
+        let offset = self.dest_range.start as isize - self.src_range.start as isize;
+
+        MapRangeResult {
+            mapped: in_ranges.intersection(&src).shift(offset),
+            unmapped: in_ranges.difference(&src),
+        }
     }
+}
 
-    fn map_range(&self, in_range: Range<usize>) -> MapRangeResult {
-        //dbg!("OneRangeMap map_range");
+// What one OneRangeMap entry did to the ids that reached it
+#[derive(Debug)]
+struct TraceStep {
+    entry: usize,
+    src_range: Range<usize>,
+    offset: isize,
+    matched: IntervalSet,
+    mapped: IntervalSet,
+}
 
-        let mut in_idx = in_range.start;
-        let mut out = vec![];
+// How ids went through one RangeMap
+#[derive(Debug)]
+struct LayerTrace {
+    map: String,
+    input: IntervalSet,
+    // Only entries that matched something
+    steps: Vec<TraceStep>,
+    // Ids no entry matched, which keep their value
+    unmapped: IntervalSet,
+    output: IntervalSet,
+}
 
-        //let mut unmapped = vec![];
+fn json_ranges(set: &IntervalSet) -> String {
+    let ranges = set.ranges.iter().map(|r| format!("[{}, {}]", r.start, r.end)).collect::<Vec<_>>();
+    format!("[{}]", ranges.join(", "))
+}
 
-        let intersection = range_intersect(&in_range, &self.src_range);
-        if intersection.is_none() {
-            return MapRangeResult {
-                mapped: vec![],
-                unmapped: vec![in_range],
-            };
+impl LayerTrace {
+    fn to_table(&self) -> String {
+        let mut out = format!("{} {:?}\n", self.map, self.input.ranges);
+        for step in &self.steps {
+            out.push_str(&format!(
+                "  entry {:>3}  src {:<24} offset {:>+12}  {:?} -> {:?}\n",
+                step.entry,
+                format!("{:?}", step.src_range),
+                step.offset,
+                step.matched.ranges,
+                step.mapped.ranges
+            ));
         }
-        let intersection = intersection.unwrap();
-
-        let unmap1 = in_range.start..intersection.start;
-        let unmap2 = intersection.end..in_range.end;
-
-        let unmapped = vec![unmap1, unmap2];
-        
-        /*dbg!(&intersection);
-        dbg!(&self);
-        dbg!(&in_range);
-        dbg!(&unmapped);*/
-
-        while in_idx < in_range.end {
-            if self.src_range.contains(&in_idx) {
-                //dbg!("The src_range", &self.src_range, "contains", in_idx);
-                //dbg!(&in_range);
-                let earliest_end = *[in_range.end, self.src_range.end].iter().min().unwrap();
-                let src_idx_start = in_idx - self.src_range.start;
-                //dbg!(src_idx_start);
-                let dest_idx_start = src_idx_start;
-                let dest_idx_end = earliest_end - self.src_range.start;
-                //let src_idx_end =
-                let range = (self.dest_range.start + dest_idx_start)
-                    ..(self.dest_range.start + dest_idx_end);
-                //dbg!(range.len());
-                //dbg!(&range);
-                in_idx += range.len();
-                //dbg!(&in_range, &self.src_range, &range, in_idx, &earliest_end);
-                out.push(range);
-            } else if self.src_range.start > in_idx {
-                // in_idx is not contained in the src range.  but maybe it's below.
-                in_idx += self.src_range.start - in_idx;
-                //dbg!("2");
-            } else {
-                in_idx = in_range.end;
-                //dbg!("3");
-            }
+        if !self.unmapped.ranges.is_empty() {
+            out.push_str(&format!("  unmapped {:?}\n", self.unmapped.ranges));
         }
+        out.push_str(&format!("  -> {:?}\n", self.output.ranges));
+        out
+    }
 
-        MapRangeResult {
-            mapped: out,
-            unmapped,
-        }
+    fn to_json(&self) -> String {
+        let steps = self
+            .steps
+            .iter()
+            .map(|step| {
+                format!(
+                    "{{\"entry\": {}, \"src_range\": [{}, {}], \"offset\": {}, \"matched\": {}, \"mapped\": {}}}",
+                    step.entry,
+                    step.src_range.start,
+                    step.src_range.end,
+                    step.offset,
+                    json_ranges(&step.matched),
+                    json_ranges(&step.mapped)
+                )
+            })
+            .collect::<Vec<_>>();
+        format!(
+            "{{\"map\": \"{}\", \"input\": {}, \"steps\": [{}], \"unmapped\": {}, \"output\": {}}}",
+            self.map.replace('\\', "\\\\").replace('"', "\\\""),
+            json_ranges(&self.input),
+            steps.join(", "),
+            json_ranges(&self.unmapped),
+            json_ranges(&self.output)
+        )
     }
 }
 
 #[derive(Debug)]
 struct RangeMap {
     name: String,
+    // The categories from the header, e.g. "seed" and "soil" for "seed-to-soil map:"
+    source: String,
+    dest: String,
     maps: Vec<OneRangeMap>,
 }
 
@@ -103,32 +219,275 @@ impl RangeMap {
         self.maps.iter().find_map(|m| m.map(id)).unwrap_or(id)
     }
 
-    fn map_range(&self, in_range: Range<usize>) -> Vec<Range<usize>> {
+    fn map_range(&self, in_ranges: &IntervalSet) -> IntervalSet {
         let init = MapRangeResult {
-            mapped: vec![],
-            unmapped: vec![in_range],
+            mapped: IntervalSet::default(),
+            unmapped: in_ranges.clone(),
         };
 
-        let mut aftermap = self.maps.iter().fold(init, |mut acc, m| {
-            let unmapped_orig = acc.unmapped;
-            acc.unmapped = vec![];
+        // Each map only sees what the maps before it left unmapped
+        let aftermap = self.maps.iter().fold(init, |acc, m| {
+            let result = m.map_range(&acc.unmapped);
+            MapRangeResult {
+                mapped: acc.mapped.union(&result.mapped),
+                unmapped: result.unmapped,
+            }
+        });
 
-            for range in unmapped_orig {
-                //dbg!(&range, &m);
-                let result = m.map_range(range);
-                //dbg!(&result);
-                acc.mapped.extend(result.mapped);
-                acc.unmapped.extend(result.unmapped);
+        aftermap.mapped.union(&aftermap.unmapped)
+    }
+
+    // map_range, recording which entry matched which ids
+    fn trace(&self, in_ranges: &IntervalSet) -> LayerTrace {
+        let mut steps = vec![];
+        let mut unmapped = in_ranges.clone();
+        let mut output = IntervalSet::default();
+
+        for (entry, m) in self.maps.iter().enumerate() {
+            let result = m.map_range(&unmapped);
+            if result.mapped.len() > 0 {
+                steps.push(TraceStep {
+                    entry,
+                    src_range: m.src_range.clone(),
+                    offset: m.dest_range.start as isize - m.src_range.start as isize,
+                    matched: unmapped.difference(&result.unmapped),
+                    mapped: result.mapped.clone(),
+                });
             }
+            output = output.union(&result.mapped);
+            unmapped = result.unmapped;
+        }
 
-            acc
-        });
+        LayerTrace {
+            map: self.name.clone(),
+            input: in_ranges.clone(),
+            steps,
+            output: output.union(&unmapped),
+            unmapped,
+        }
+    }
 
-        //dbg!(&aftermap);
+    // Every id that maps into ids, i.e. map_range run backwards
+    fn preimage(&self, ids: &IntervalSet) -> IntervalSet {
+        let mut covered = IntervalSet::default();
+        let mut sources = IntervalSet::default();
+
+        for m in &self.maps {
+            let src = IntervalSet::from_range(m.src_range.clone());
+            let dest = IntervalSet::from_range(m.dest_range.clone());
+            let offset = m.src_range.start as isize - m.dest_range.start as isize;
+            // Ids covered by an earlier map never reach this one
+            let from_here = ids.intersection(&dest).shift(offset).difference(&covered);
+            sources = sources.union(&from_here);
+            covered = covered.union(&src);
+        }
 
-        aftermap.mapped.extend(aftermap.unmapped);
+        // Unmapped ids map to themselves
+        sources.union(&ids.difference(&covered))
+    }
+}
+
+// One linear piece of a piecewise map: src maps onto dest_start.. in order
+#[derive(Debug, Clone)]
+struct Piece {
+    src: Range<usize>,
+    dest_start: usize,
+}
+
+impl Piece {
+    fn offset(&self) -> isize {
+        self.dest_start as isize - self.src.start as isize
+    }
 
-        aftermap.mapped
+    fn dest(&self) -> Range<usize> {
+        self.dest_start..(self.dest_start + self.src.len())
+    }
+}
+
+// A map over all ids as sorted, non-overlapping pieces that cover 0..usize::MAX
+#[derive(Debug, Clone)]
+struct PiecewiseMap {
+    pieces: Vec<Piece>,
+}
+
+impl PiecewiseMap {
+    fn identity() -> PiecewiseMap {
+        PiecewiseMap {
+            pieces: vec![Piece {
+                src: 0..usize::MAX,
+                dest_start: 0,
+            }],
+        }
+    }
+
+    fn from_range_map(range_map: &RangeMap) -> PiecewiseMap {
+        let mut cuts = range_map
+            .maps
+            .iter()
+            .flat_map(|m| [m.src_range.start, m.src_range.end])
+            .chain([0, usize::MAX])
+            .collect::<Vec<_>>();
+        cuts.sort();
+        cuts.dedup();
+
+        // Like RangeMap::map, the first map that contains an id wins
+        let pieces = cuts
+            .iter()
+            .tuple_windows()
+            .map(|(&start, &end)| Piece {
+                src: start..end,
+                dest_start: range_map.map(start),
+            })
+            .collect();
+
+        PiecewiseMap { pieces }.merged()
+    }
+
+    // Join neighboring pieces that shift by the same amount
+    fn merged(self) -> PiecewiseMap {
+        let mut pieces: Vec<Piece> = vec![];
+        for piece in self.pieces {
+            match pieces.last_mut() {
+                Some(last) if last.src.end == piece.src.start && last.offset() == piece.offset() => {
+                    last.src.end = piece.src.end
+                }
+                _ => pieces.push(piece),
+            }
+        }
+        PiecewiseMap { pieces }
+    }
+
+    // Index of the piece containing id
+    fn find(&self, id: usize) -> usize {
+        self.pieces.partition_point(|p| p.src.end <= id)
+    }
+
+    fn map(&self, id: usize) -> usize {
+        let piece = &self.pieces[self.find(id)];
+        piece.dest_start + (id - piece.src.start)
+    }
+
+    // The pieces of this map restricted to range
+    fn pieces_in(&self, range: Range<usize>) -> impl Iterator<Item = Piece> + '_ {
+        self.pieces[self.find(range.start)..]
+            .iter()
+            .take_while(move |p| p.src.start < range.end)
+            .filter_map(move |p| {
+                let src = range_intersect(&p.src, &range)?;
+                Some(Piece {
+                    dest_start: p.dest_start + (src.start - p.src.start),
+                    src,
+                })
+            })
+    }
+
+    // self followed by next
+    fn then(&self, next: &PiecewiseMap) -> PiecewiseMap {
+        let pieces = self
+            .pieces
+            .iter()
+            .flat_map(|p| {
+                next.pieces_in(p.dest()).map(move |q| {
+                    let start = p.src.start + (q.src.start - p.dest_start);
+                    Piece {
+                        src: start..(start + q.src.len()),
+                        dest_start: q.dest_start,
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        let mut map = PiecewiseMap { pieces };
+        map.pieces.sort_by_key(|p| p.src.start);
+        map.merged()
+    }
+
+    // The lowest id that any of the ranges maps to
+    fn lowest(&self, ranges: &[Range<usize>]) -> Option<usize> {
+        ranges
+            .iter()
+            .flat_map(|r| self.pieces_in(r.clone()))
+            .map(|p| p.dest_start)
+            .min()
+    }
+
+    fn inverse(&self) -> InverseMap {
+        let mut cuts = self
+            .pieces
+            .iter()
+            .flat_map(|p| {
+                let dest = p.dest();
+                [dest.start, dest.end]
+            })
+            .collect::<Vec<_>>();
+        cuts.sort();
+        cuts.dedup();
+
+        let mut by_dest = self.pieces.clone();
+        by_dest.sort_by_key(|p| p.dest_start);
+
+        let segments = cuts
+            .iter()
+            .tuple_windows()
+            .map(|(&start, &end)| {
+                let sources = by_dest
+                    .iter()
+                    .take_while(|p| p.dest_start <= start)
+                    .filter(|p| p.dest().end >= end)
+                    .map(|p| p.src.start + (start - p.dest_start))
+                    .collect::<Vec<_>>();
+                (start..end, sources)
+            })
+            .filter(|(_, sources)| !sources.is_empty())
+            .collect();
+
+        InverseMap { segments }
+    }
+}
+
+// Where ids came from: the destination axis cut into segments, each with the
+// start of every source range that lands on it.  Maps need not be one-to-one,
+// so an id can have several sources or none.
+#[derive(Debug)]
+struct InverseMap {
+    segments: Vec<(Range<usize>, Vec<usize>)>,
+}
+
+impl InverseMap {
+    fn sources(&self, id: usize) -> Vec<usize> {
+        let i = self.segments.partition_point(|(r, _)| r.end <= id);
+        match self.segments.get(i) {
+            Some((r, starts)) if r.contains(&id) => starts.iter().map(|s| s + (id - r.start)).collect(),
+            _ => vec![],
+        }
+    }
+}
+
+// Something wrong with the structure of an almanac
+#[derive(Debug)]
+enum Problem {
+    // Two ranges of one map share source ids
+    Overlap {
+        map: String,
+        first: Range<usize>,
+        second: Range<usize>,
+    },
+    // More than one map converts from (or to) the same category
+    DuplicateCategory { category: String, maps: Vec<String> },
+    // No chain of maps leads from one category to the other
+    BrokenChain { from: String, to: String },
+}
+
+impl std::fmt::Display for Problem {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Problem::Overlap { map, first, second } => {
+                write!(f, "{map} has overlapping sources {first:?} and {second:?}")
+            }
+            Problem::DuplicateCategory { category, maps } => {
+                write!(f, "{category} appears in more than one map: {}", maps.join(", "))
+            }
+            Problem::BrokenChain { from, to } => write!(f, "No chain of maps from {from} to {to}"),
+        }
     }
 }
 
@@ -139,28 +498,174 @@ struct Stuff {
 }
 
 impl Stuff {
+    // Shortest sequence of maps from one category to another, as (map index,
+    // forward) steps.  Maps can only be used backwards if allow_reverse.
+    fn route(&self, from: &str, to: &str, allow_reverse: bool) -> Result<Vec<(usize, bool)>, String> {
+        let mut prev: std::collections::HashMap<&str, Option<(usize, bool)>> = std::collections::HashMap::new();
+        prev.insert(from, None);
+        let mut queue = std::collections::VecDeque::from([from]);
+
+        while let Some(category) = queue.pop_front() {
+            if category == to {
+                break;
+            }
+            for (i, m) in self.maps.iter().enumerate() {
+                let mut steps = vec![];
+                if m.source == category {
+                    steps.push((m.dest.as_str(), true));
+                }
+                if allow_reverse && m.dest == category {
+                    steps.push((m.source.as_str(), false));
+                }
+                for (next, forward) in steps {
+                    if !prev.contains_key(next) {
+                        prev.insert(next, Some((i, forward)));
+                        queue.push_back(next);
+                    }
+                }
+            }
+        }
+
+        if !prev.contains_key(to) {
+            return Err(format!("No route from {from} to {to}"));
+        }
+
+        let mut path = vec![];
+        let mut category = to;
+        while let Some((i, forward)) = prev[category] {
+            path.push((i, forward));
+            let m = &self.maps[i];
+            category = if forward { &m.source } else { &m.dest };
+        }
+        path.reverse();
+        Ok(path)
+    }
+
+    // The maps from seed to location, in order
+    fn chain(&self) -> Result<Chain<'_>, Problem> {
+        let route = self.route("seed", "location", false).map_err(|_| Problem::BrokenChain {
+            from: "seed".to_string(),
+            to: "location".to_string(),
+        })?;
+        Ok(Chain {
+            maps: route.into_iter().map(|(i, _)| &self.maps[i]).collect(),
+        })
+    }
+
+    // Convert ids from one category to another, going through maps
+    // backwards where needed
+    fn convert(&self, from: &str, to: &str, ids: &IntervalSet) -> Result<IntervalSet, String> {
+        Ok(self
+            .route(from, to, true)?
+            .into_iter()
+            .fold(ids.clone(), |ids, (i, forward)| {
+                if forward {
+                    self.maps[i].map_range(&ids)
+                } else {
+                    self.maps[i].preimage(&ids)
+                }
+            }))
+    }
+
+    fn validate(&self) -> Vec<Problem> {
+        let mut problems = vec![];
+
+        for m in &self.maps {
+            let mut srcs = m.maps.iter().map(|o| o.src_range.clone()).collect::<Vec<_>>();
+            srcs.sort_by_key(|r| r.start);
+            let mut widest: Option<Range<usize>> = None;
+            for r in srcs {
+                match &widest {
+                    Some(w) if range_intersect(w, &r).is_some() => problems.push(Problem::Overlap {
+                        map: m.name.clone(),
+                        first: w.clone(),
+                        second: r.clone(),
+                    }),
+                    _ => (),
+                }
+                if widest.as_ref().is_none_or(|w| r.end > w.end) {
+                    widest = Some(r);
+                }
+            }
+        }
+
+        for category in self.maps.iter().flat_map(|m| [&m.source, &m.dest]).unique() {
+            for field in [|m: &RangeMap| m.source.clone(), |m: &RangeMap| m.dest.clone()] {
+                let maps = self
+                    .maps
+                    .iter()
+                    .filter(|m| field(m) == *category)
+                    .map(|m| m.name.clone())
+                    .collect::<Vec<_>>();
+                if maps.len() > 1 {
+                    problems.push(Problem::DuplicateCategory {
+                        category: category.clone(),
+                        maps,
+                    });
+                }
+            }
+        }
+
+        if let Err(problem) = self.chain() {
+            problems.push(problem);
+        }
+
+        problems
+    }
+}
+
+// The maps from seed to location, found once so every seed doesn't search
+// for them again
+struct Chain<'a> {
+    maps: Vec<&'a RangeMap>,
+}
+
+impl Chain<'_> {
     fn map(&self, id: usize) -> usize {
         self.maps.iter().fold(id, |id, m| m.map(id))
     }
 
-    fn map_range(&self, in_range: Range<usize>) -> Vec<Range<usize>> {
-        let init = vec![in_range];
+    // The whole chain of maps as a single map
+    fn compose(&self) -> PiecewiseMap {
+        self.maps
+            .iter()
+            .fold(PiecewiseMap::identity(), |acc, m| acc.then(&PiecewiseMap::from_range_map(m)))
+    }
 
-        self.maps.iter()
-        //.take(1) // ONE MAP
-        .fold(init, |ranges, m| {
-            ranges
-                .iter()
-                .flat_map(|range| m.map_range(range.clone()))
-                .collect::<Vec<_>>()
-        })
+    fn trace(&self, in_ranges: &IntervalSet) -> Vec<LayerTrace> {
+        let mut traces: Vec<LayerTrace> = vec![];
+        for m in &self.maps {
+            let input = traces.last().map_or(in_ranges, |t| &t.output);
+            let trace = m.trace(input);
+            traces.push(trace);
+        }
+        traces
+    }
+
+    // The ids at every stage, starting with in_ranges and ending with locations
+    fn map_range(&self, in_ranges: &IntervalSet) -> Vec<IntervalSet> {
+        let mut stages = vec![in_ranges.clone()];
+        for m in &self.maps {
+            let next = m.map_range(stages.last().unwrap());
+            stages.push(next);
+        }
+        stages
+    }
+}
+
+// "seed-to-soil map:" -> ("seed", "soil")
+fn parse_header(line: &str) -> (String, String) {
+    let categories = line.strip_suffix(" map:").unwrap_or_else(|| panic!("Invalid header: {line}"));
+    match categories.split_once("-to-") {
+        Some((source, dest)) => (source.to_string(), dest.to_string()),
+        None => panic!("Invalid header: {line}"),
     }
 }
 
 fn parse(stdin: std::io::Stdin) -> Stuff {
     let mut lines = stdin.lines();
     let seeds = lines
-        .nth(0)
+        .next()
         .unwrap()
         .unwrap()
         .split(": ")
@@ -174,6 +679,8 @@ fn parse(stdin: std::io::Stdin) -> Stuff {
 
     let mut map = RangeMap {
         name: String::new(),
+        source: String::new(),
+        dest: String::new(),
         maps: Vec::new(),
     };
 
@@ -183,8 +690,11 @@ fn parse(stdin: std::io::Stdin) -> Stuff {
                 maps.push(map);
             };
 
+            let (source, dest) = parse_header(&line);
             map = RangeMap {
                 name: line,
+                source,
+                dest,
                 maps: Vec::new(),
             };
         } else {
@@ -212,21 +722,54 @@ fn parse(stdin: std::io::Stdin) -> Stuff {
 }
 
 fn main() {
+    // --trace=SEED or --trace=START..END follows seeds through every map,
+    // printed as a table or, with --json, as JSON
+    let mut trace = None;
+    let mut json = false;
+    for arg in std::env::args().skip(1) {
+        if arg == "--json" {
+            json = true;
+        } else if let Some(seeds) = arg.strip_prefix("--trace=") {
+            let parse_id = |s: &str| s.parse::<usize>().unwrap_or_else(|_| panic!("Invalid seed: {s}"));
+            trace = Some(match seeds.split_once("..") {
+                Some((start, end)) => parse_id(start)..parse_id(end),
+                None => parse_id(seeds)..parse_id(seeds) + 1,
+            });
+        } else {
+            panic!("Unknown option {arg}");
+        }
+    }
+
     let stuff = parse(std::io::stdin());
 
     //dbg!(&stuff);
 
-    let mapped_seeds = stuff
-        .seeds
-        .iter()
-        .map(|seed| stuff.map(*seed))
-        .collect::<Vec<_>>();
-    //dbg!(&mapped_seeds);
+    for problem in stuff.validate() {
+        println!("Problem: {problem}");
+    }
+
+    // Nothing can be followed to a location without the whole chain
+    let Ok(chain) = stuff.chain() else {
+        return;
+    };
+
+    if let Some(seeds) = trace {
+        let traces = chain.trace(&IntervalSet::from_range(seeds));
+        if json {
+            let layers = traces.iter().map(|t| t.to_json()).collect::<Vec<_>>();
+            println!("[{}]", layers.join(",\n "));
+        } else {
+            for t in &traces {
+                print!("{}", t.to_table());
+            }
+        }
+        return;
+    }
 
     let themin = stuff
         .seeds
         .iter()
-        .map(|seed| stuff.map(*seed))
+        .map(|seed| chain.map(*seed))
         .min()
         .unwrap();
 
@@ -245,20 +788,34 @@ fn main() {
 
     /*println!(
         "The new min: {}",
-        new_seeds.iter().map(|seed| stuff.map(*seed)).min().unwrap()
+        new_seeds.iter().map(|seed| chain.map(*seed)).min().unwrap()
     );*/
 
-    let new_seeds_location_ranges = new_seeds
-        .iter()
-        .flat_map(|seed| stuff.map_range(seed.clone()))
-        .filter(|r| r.len() > 0)
-        .collect::<Vec<_>>();
+    let stages = chain.map_range(&IntervalSet::from_ranges(new_seeds.iter().cloned()));
 
-    //dbg!(&new_seeds_location_ranges);
+    let counts = stages.iter().map(|s| s.len().to_string()).collect::<Vec<_>>();
+    println!("Ranges at each stage: {}", counts.join(" -> "));
 
-    let thenewmin = new_seeds_location_ranges.iter().map(|r| r.start).min().unwrap();
+    let thenewmin = stages.last().unwrap().min().unwrap();
 
     println!("The new min: {}", thenewmin);
 
+    let composed = chain.compose();
+    println!("The composed map has {} pieces", composed.pieces.len());
+    assert_eq!(stuff.seeds.iter().map(|s| composed.map(*s)).min(), Some(themin));
+    assert_eq!(composed.lowest(&new_seeds), Some(thenewmin));
+
+    let inverse = composed.inverse();
+    println!("Seeds at location {}: {:?}", thenewmin, inverse.sources(thenewmin));
+
+    let seeds = stuff
+        .convert("location", "seed", &IntervalSet::from_range(thenewmin..thenewmin + 1))
+        .unwrap();
+    assert_eq!(seeds, IntervalSet::from_ranges(inverse.sources(thenewmin).iter().map(|s| *s..*s + 1)));
+    let soil = stuff
+        .convert("location", "soil", &IntervalSet::from_range(thenewmin..thenewmin + 1))
+        .unwrap();
+    println!("Soil at location {}: {:?}", thenewmin, soil.ranges);
+
     ////let wat = stuff.map_range(new_seeds[0].clone());
 }
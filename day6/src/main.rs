@@ -1,79 +1,291 @@
 #[derive(Debug)]
 struct Race {
-    time: usize,
-    distance: usize,
+    time: u128,
+    distance: u128,
+}
+
+// floor(sqrt(n)), exactly
+fn isqrt(n: u128) -> u128 {
+    if n < 2 {
+        return n;
+    }
+    // Start from the f64 estimate and correct it, since f64 can be off by a
+    // lot for numbers this big
+    let mut x = (n as f64).sqrt() as u128;
+    while x.checked_mul(x).is_none_or(|sq| sq > n) {
+        x -= 1;
+    }
+    while (x + 1).checked_mul(x + 1).is_some_and(|sq| sq <= n) {
+        x += 1;
+    }
+    x
 }
 
 impl Race {
-    fn win(&self, charge_ms: usize) -> bool {
+    fn win(&self, charge_ms: u128) -> bool {
         let go_time = self.time - charge_ms;
-        let speed = 1 * charge_ms;
-        let boat_goes_distance = go_time * speed;
-
-        boat_goes_distance > self.distance
+        let speed = charge_ms;
+        // Too far to even represent is certainly far enough
+        go_time.checked_mul(speed).is_none_or(|boat_goes_distance| boat_goes_distance > self.distance)
     }
 
-    fn winners(&self) -> std::ops::Range<usize> {
+    // The smallest winning charge time, if there is one
+    fn first_winner(&self) -> Option<u128> {
         /*
          * OK, looking at win code.
          *
          *   boat_goes_distance > self.distance
-         * = go_time * speed > self.distance
          * = (self.time - charge_ms) * charge_ms > self.distance
+         * = (self.time - 2 * charge_ms)^2 < self.time^2 - 4 * self.distance
          *
-         *
-         * self.time and self.distance are constants here.  So solve for charge_ms.
-         * = self.time * charge_ms - charge_ms^2 > self.distance.
-         * = charge_ms(self.time - charge_ms) > self.distance
-         *
-         * Quadratic... true if charge_ms < self.time/2 - sqrt(self.time^2 -4*self.distance)/2
+         * So with s = isqrt(self.time^2 - 4 * self.distance), the winners are
+         * those with |self.time - 2 * charge_ms| <= s, except that when the
+         * square root is exact the boundary is a tie, which doesn't win.
          */
+        let half = self.time / 2;
+        let candidate = match self
+            .time
+            .checked_mul(self.time)
+            .zip(self.distance.checked_mul(4))
+        {
+            Some((t2, d4)) => {
+                let disc = t2.checked_sub(d4)?;
+                let s = isqrt(disc);
+                let max_offset = if s * s == disc { s.checked_sub(1)? } else { s };
+                // Smallest charge_ms with self.time - 2 * charge_ms <= max_offset
+                self.time.saturating_sub(max_offset).div_ceil(2)
+            }
+            // Too big to square, so binary search for the boundary instead;
+            // win is monotonic on 0..=half
+            None => {
+                let (mut lo, mut hi) = (0, half);
+                while lo < hi {
+                    let mid = lo + (hi - lo) / 2;
+                    if self.win(mid) {
+                        hi = mid;
+                    } else {
+                        lo = mid + 1;
+                    }
+                }
+                lo
+            }
+        };
 
-        //dbg!((0..self.time).filter(|t| self.win(*t)).collect::<Vec<_>>());
-
-        let a = -1.0;
-        let b = self.time as f64;
-        let c = -(self.distance as f64);
+        // Boundary correction: make sure candidate is the exact first winner
+        let mut c = candidate.min(half);
+        while c > 0 && self.win(c - 1) {
+            c -= 1;
+        }
+        while c < half && !self.win(c) {
+            c += 1;
+        }
+        self.win(c).then_some(c)
+    }
 
-        // Calculate the discriminant
-        let discriminant = b.powi(2) - 4.0 * a * c;
+    fn winners(&self) -> std::ops::Range<u128> {
+        // Charging for c or for self.time - c goes the same distance
+        match self.first_winner() {
+            Some(first) => first..(self.time - first + 1),
+            None => 0..0,
+        }
+    }
 
-        // Check if the discriminant is non-negative
-        if discriminant >= 0.0 {
-            let sqrt_discriminant = discriminant.sqrt();
-            let root2 = (-b - sqrt_discriminant) / (2.0 * a);
-            let root1 = (-b + sqrt_discriminant) / (2.0 * a);
+    fn num_winners(&self) -> u128 {
+        let r = self.winners();
+        r.end - r.start
+    }
+}
 
-            // There's probably a better way to do this... we want ceil/floor,
-            // except that exact integers don't count because of the > in the
-            // win function.
-            let root1 = root1 + 0.0000001;
-            let root2 = root2 - 0.0000001;
+// How a boat turns charge time into speed
+#[derive(Debug, Clone)]
+enum Model {
+    // speed = rate * charge_ms
+    Linear { rate: u128 },
+    // speed = rate * charge_ms, but never more than max_speed
+    Capped { rate: u128, max_speed: u128 },
+    // The first ms of charging adds rate, and every ms after adds decay less,
+    // down to nothing
+    Diminishing { rate: u128, decay: u128 },
+}
 
-            let root1_int = root1.ceil() as usize;
-            let root2_int = root2.floor() as usize;
+impl Model {
+    // --boat=linear:RATE, --boat=capped:RATE:MAX or --boat=diminishing:RATE:DECAY
+    fn from_string(s: &str) -> Result<Model, String> {
+        let parts = s.split(':').collect::<Vec<_>>();
+        let num = |i: usize| -> Result<u128, String> {
+            parts
+                .get(i)
+                .ok_or(format!("Missing parameter in {s}"))?
+                .parse::<u128>()
+                .map_err(|e| format!("Bad parameter in {s}: {e}"))
+        };
+        match parts[0] {
+            "linear" => Ok(Model::Linear { rate: num(1)? }),
+            "capped" => Ok(Model::Capped {
+                rate: num(1)?,
+                max_speed: num(2)?,
+            }),
+            "diminishing" => Ok(Model::Diminishing {
+                rate: num(1)?,
+                decay: num(2)?,
+            }),
+            _ => Err(format!("Unknown boat model {s}")),
+        }
+    }
 
-            dbg!(root1, root1_int, root2, root2_int);
+    fn speed(&self, charge_ms: u128) -> u128 {
+        match *self {
+            Model::Linear { rate } => rate.saturating_mul(charge_ms),
+            Model::Capped { rate, max_speed } => rate.saturating_mul(charge_ms).min(max_speed),
+            Model::Diminishing { rate, decay } => {
+                // Only the first `useful` ms add anything
+                let useful = if decay == 0 { charge_ms } else { rate.div_ceil(decay).min(charge_ms) };
+                let triangle = useful.saturating_mul(useful.saturating_sub(1)) / 2;
+                useful.saturating_mul(rate).saturating_sub(decay.saturating_mul(triangle))
+            }
+        }
+    }
 
-            let r = root1_int..root2_int + 1;
-            /*let dbg = (0..self.time).filter(|t| self.win(*t)).collect::<Vec<_>>().len();
+    fn distance(&self, time: u128, charge_ms: u128) -> u128 {
+        (time - charge_ms).saturating_mul(self.speed(charge_ms))
+    }
 
+    // Diminishing only: the charge time after which more charging adds no
+    // speed, capped at time.  Up to there the distance is a cubic.
+    fn useful_charge(rate: u128, decay: u128, time: u128) -> u128 {
+        if decay == 0 {
+            time
+        } else {
+            rate.div_ceil(decay).min(time)
+        }
+    }
 
-            dbg!(&r);
+    // The earliest charge time in 0..=end with the most distance, where the
+    // distance rises and then falls over that range.  That's the case up to
+    // useful_charge: (time - c) and the speed are both concave, so their
+    // product is log-concave and has a single peak.
+    fn peak(&self, time: u128, end: u128) -> u128 {
+        let (mut lo, mut hi) = (0, end);
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            if self.distance(time, mid + 1) > self.distance(time, mid) {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+        lo
+    }
 
-            assert!(dbg!(dbg) == dbg!(r.len()));
-            */
-            r
-        } else {
-            0..0
+    // First charge time in lo..=hi that wins, or hi + 1, where the distance
+    // only rises (rising) or only falls (!rising) over that range
+    fn boundary(&self, race: &Race, lo: u128, hi: u128, rising: bool) -> u128 {
+        let (mut lo, mut hi) = (lo, hi + 1);
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            if (self.distance(race.time, mid) > race.distance) == rising {
+                hi = mid;
+            } else {
+                lo = mid + 1;
+            }
         }
+        lo
+    }
+
+    // Distance for every charge time from 0 to time
+    fn curve(&self, time: u128) -> Vec<u128> {
+        (0..=time).map(|c| self.distance(time, c)).collect()
+    }
+
+    // Winning charge times, as sorted, non-overlapping ranges
+    fn winners(&self, race: &Race) -> Vec<std::ops::Range<u128>> {
+        // rate * x > distance exactly when x > distance / rate
+        let linear = |rate: u128| {
+            if rate == 0 {
+                return 0..0;
+            }
+            Race {
+                time: race.time,
+                distance: race.distance / rate,
+            }
+            .winners()
+        };
 
-        //(0..self.time).filter(|t| self.win(*t)).collect::<Vec<_>>()
-        //(0..self.time).filter(|t| (*t as f32) > bound).collect::<Vec<_>>()
+        let ranges = match *self {
+            Model::Linear { rate } => vec![linear(rate)],
+            Model::Capped { rate, max_speed } if rate > 0 && max_speed > 0 => {
+                // Linear until the cap, then the speed is fixed and less
+                // charging is better
+                let capped_at = max_speed.div_ceil(rate).min(race.time + 1);
+                let before = linear(rate);
+                let before = before.start..before.end.min(capped_at);
+                let after = capped_at..race.time.saturating_sub(race.distance / max_speed).max(capped_at);
+                match (before.is_empty(), after.is_empty()) {
+                    (true, _) => vec![after],
+                    (_, true) => vec![before],
+                    _ if before.end == after.start => {
+                        let joined = before.start..after.end;
+                        vec![joined]
+                    }
+                    _ => vec![before, after],
+                }
+            }
+            Model::Capped { .. } => vec![],
+            Model::Diminishing { rate, decay } if rate > 0 => {
+                // A cubic with one peak up to useful, then a fixed speed where
+                // less charging is better, like Capped's tail
+                let useful = Self::useful_charge(rate, decay, race.time);
+                let peak = self.peak(race.time, useful);
+                let before = if self.distance(race.time, peak) > race.distance {
+                    let first = self.boundary(race, 0, peak, true);
+                    let end = self.boundary(race, peak, useful, false);
+                    first..end
+                } else {
+                    0..0
+                };
+                let after = if useful < race.time {
+                    let speed = self.speed(useful);
+                    (useful + 1)..race.time.saturating_sub(race.distance / speed).max(useful + 1)
+                } else {
+                    0..0
+                };
+                match (before.is_empty(), after.is_empty()) {
+                    (true, _) => vec![after],
+                    (_, true) => vec![before],
+                    _ if before.end == after.start => {
+                        let joined = before.start..after.end;
+                        vec![joined]
+                    }
+                    _ => vec![before, after],
+                }
+            }
+            Model::Diminishing { .. } => vec![],
+        };
+        ranges.into_iter().filter(|r| !r.is_empty()).collect()
     }
 
-    fn num_winners(&self) -> usize {
-        dbg!(self.winners()).len()
+    fn num_winners(&self, race: &Race) -> u128 {
+        self.winners(race).iter().map(|r| r.end - r.start).sum()
+    }
+
+    // The charge time that goes furthest (the earliest, if there's a tie)
+    fn optimal_charge(&self, time: u128) -> u128 {
+        match *self {
+            Model::Linear { rate } if rate > 0 => time / 2,
+            Model::Capped { rate, max_speed } if rate > 0 && max_speed > 0 => {
+                // Rising until time / 2 or the cap, falling after the cap
+                let capped_at = max_speed.div_ceil(rate);
+                [time / 2, capped_at.saturating_sub(1), capped_at]
+                    .into_iter()
+                    .filter(|c| *c <= time)
+                    .min_by_key(|c| (std::cmp::Reverse(self.distance(time, *c)), *c))
+                    .unwrap()
+            }
+            // The tail after useful only falls
+            Model::Diminishing { rate, decay } if rate > 0 => self.peak(time, Self::useful_charge(rate, decay, time)),
+            // Nothing ever moves
+            _ => 0,
+        }
     }
 }
 
@@ -88,7 +300,7 @@ fn parse(stdin: std::io::Stdin) -> Races {
         .map(|l| {
             l.split_ascii_whitespace()
                 .skip(1)
-                .map(|s| s.parse::<usize>().unwrap())
+                .map(|s| s.parse::<u128>().unwrap())
                 .collect::<Vec<_>>()
         })
         .collect::<Vec<_>>();
@@ -105,6 +317,20 @@ fn parse(stdin: std::io::Stdin) -> Races {
 }
 
 fn main() {
+    // --boat=MODEL races an extra boat (see Model::from_string), and --curve
+    // prints each boat's distance for every charge time in the part 1 races
+    let mut boats = vec![];
+    let mut curve = false;
+    for arg in std::env::args().skip(1) {
+        if arg == "--curve" {
+            curve = true;
+        } else if let Some(model) = arg.strip_prefix("--boat=") {
+            boats.push(Model::from_string(model).unwrap_or_else(|e| panic!("{e}")));
+        } else {
+            panic!("Unknown option {arg}");
+        }
+    }
+
     let races = parse(std::io::stdin());
 
     println!("Races: {:?}", races);
@@ -114,7 +340,7 @@ fn main() {
         .map(|r| r.winners())
         .for_each(|w| println!("{:?}", w));
 
-    let p1 = races.iter().map(|r| r.num_winners()).product::<usize>();
+    let p1 = races.iter().map(|r| r.num_winners()).product::<u128>();
     println!("Part 1: {p1}");
 
     let p2_race = Race {
@@ -122,16 +348,126 @@ fn main() {
             .iter()
             .map(|r| r.time.to_string())
             .collect::<String>()
-            .parse::<usize>()
+            .parse::<u128>()
             .unwrap(),
         distance: races
             .iter()
             .map(|r| r.distance.to_string())
             .collect::<String>()
-            .parse::<usize>()
+            .parse::<u128>()
             .unwrap(),
     };
 
     println!("Part 2 race: {:?}", p2_race);
     println!("Answer: {}", p2_race.num_winners());
+
+    for boat in &boats {
+        println!("Boat {:?}:", boat);
+        for race in races.iter().chain(std::iter::once(&p2_race)) {
+            println!(
+                "  {:?}: {} winning charge times, best charge {}",
+                race,
+                boat.num_winners(race),
+                boat.optimal_charge(race.time)
+            );
+        }
+        if curve {
+            for race in &races {
+                println!("  Distances for time {}: {:?}", race.time, boat.curve(race.time));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    impl Model {
+        fn brute_winners(&self, race: &Race) -> Vec<std::ops::Range<u128>> {
+            let mut ranges: Vec<std::ops::Range<u128>> = vec![];
+            for c in (0..=race.time).filter(|c| self.distance(race.time, *c) > race.distance) {
+                match ranges.last_mut() {
+                    Some(last) if last.end == c => last.end += 1,
+                    _ => ranges.push(c..c + 1),
+                }
+            }
+            ranges
+        }
+
+        fn brute_optimal(&self, time: u128) -> u128 {
+            // The earliest of the best
+            (0..=time).rev().max_by_key(|c| self.distance(time, *c)).unwrap()
+        }
+    }
+
+    const MODELS: &[&str] = &[
+        "linear:1",
+        "linear:3",
+        "linear:0",
+        "capped:2:7",
+        "capped:1:1000",
+        "capped:5:1",
+        "capped:0:5",
+        "diminishing:10:3",
+        "diminishing:4:0",
+        "diminishing:7:7",
+        "diminishing:0:2",
+        "diminishing:100:1",
+        "diminishing:1:1",
+        "diminishing:25:4",
+        "diminishing:9:2",
+    ];
+
+    #[test]
+    fn isqrt_is_exact() {
+        for n in 0..10_000u128 {
+            let s = isqrt(n);
+            assert!(s * s <= n && (s + 1) * (s + 1) > n, "{n}");
+        }
+        for s in [u64::MAX as u128, (1 << 63) + 12345] {
+            assert_eq!(isqrt(s * s), s);
+            assert_eq!(isqrt(s * s - 1), s - 1);
+        }
+    }
+
+    #[test]
+    fn race_winners_match_win() {
+        for time in 0..=60 {
+            for distance in 0..=time * time / 4 + 2 {
+                let race = Race { time, distance };
+                let brute = (0..=time).filter(|c| race.win(*c)).collect::<Vec<_>>();
+                assert_eq!(race.winners().collect::<Vec<_>>(), brute, "{race:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn model_winners_match_brute_force() {
+        for model in MODELS.iter().map(|m| Model::from_string(m).unwrap()) {
+            for time in 0..=40 {
+                // Every distance the boat can reach, and either side of it
+                let mut distances = model
+                    .curve(time)
+                    .iter()
+                    .flat_map(|d| [d.saturating_sub(1), *d, d + 1])
+                    .collect::<Vec<_>>();
+                distances.sort();
+                distances.dedup();
+                for distance in distances {
+                    let race = Race { time, distance };
+                    assert_eq!(model.winners(&race), model.brute_winners(&race), "{model:?} {race:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn optimal_charge_matches_brute_force() {
+        for model in MODELS.iter().map(|m| Model::from_string(m).unwrap()) {
+            for time in 0..=200 {
+                assert_eq!(model.optimal_charge(time), model.brute_optimal(time), "{model:?} {time}");
+            }
+        }
+    }
 }
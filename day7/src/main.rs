@@ -1,122 +1,692 @@
-use std::{collections::HashMap, cmp::Ordering};
+use std::{cmp::Ordering, collections::HashMap};
 
+// How hands are compared when their types are equal
+#[derive(Debug, Clone, Copy)]
+enum TieBreak {
+    // Compare cards left to right, as dealt
+    InOrder,
+    // Compare cards from strongest to weakest, like poker high cards
+    Sorted,
+    // Compare the biggest groups of labels first, then the strongest, like
+    // poker kickers
+    Grouped,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Card {
+    label: char,
+    suit: Option<char>,
+}
+
+// Decides what type of hand some cards make
+trait Classifier: std::fmt::Debug + Sync {
+    fn classify(&self, rules: &Rules, cards: &[Card]) -> HandType;
+
+    // Whether the strongest label counts as the weakest in this hand, like
+    // the ace in an A2345 straight
+    fn strongest_is_low(&self, _rules: &Rules, _cards: &[Card]) -> bool {
+        false
+    }
+}
+
+// Camel cards: only groups of labels count
 #[derive(Debug)]
-struct Hand {
-    cards: [char; 5]
+struct CamelCards;
+
+// Poker: groups of labels, plus straights and flushes in five-card hands
+#[derive(Debug)]
+struct Poker;
+
+static CAMEL_CARDS: CamelCards = CamelCards;
+static POKER: Poker = Poker;
+
+impl Classifier for CamelCards {
+    // Wild cards always do best by joining the biggest group of labels
+    fn classify(&self, rules: &Rules, cards: &[Card]) -> HandType {
+        let mut counter = HashMap::new();
+        let mut wild = 0;
+        for c in cards {
+            if rules.wild.contains(&c.label) {
+                wild += 1;
+            } else {
+                *counter.entry(c.label).or_insert(0) += 1;
+            }
+        }
+
+        let mut counts = counter.into_values().collect::<Vec<_>>();
+        counts.sort_by(|a, b| b.cmp(a));
+        match counts.first_mut() {
+            Some(biggest) => *biggest += wild,
+            None => counts.push(wild),
+        }
+
+        HandType::of_counts(&counts)
+    }
+}
+
+impl Poker {
+    // Wild cards can take any suit, so only the other cards need to match
+    fn can_flush(rules: &Rules, cards: &[Card]) -> bool {
+        let mut suits = cards.iter().filter(|c| !rules.wild.contains(&c.label)).map(|c| c.suit);
+        match suits.next() {
+            Some(first) => first.is_some() && suits.all(|s| s == first),
+            None => !rules.suits.is_empty(),
+        }
+    }
+
+    // Five distinct labels next to each other in the rank, where the
+    // strongest label can also go below the weakest (A2345).  Wild cards
+    // fill the gaps, but only with labels that aren't wild themselves.
+    // The strengths of the best straight the cards can make, if any
+    fn best_straight(rules: &Rules, cards: &[Card]) -> Option<Vec<usize>> {
+        let top = rules.rank.chars().count();
+        let mut strengths = cards
+            .iter()
+            .filter(|c| !rules.wild.contains(&c.label))
+            .map(|c| rules.strength(c.label))
+            .collect::<Vec<_>>();
+        strengths.sort();
+        strengths.dedup();
+        if cards.len() != 5
+            || strengths.len() != cards.len() - cards.iter().filter(|c| rules.wild.contains(&c.label)).count()
+        {
+            return None;
+        }
+
+        // Weakest first
+        let windows = (top > 5)
+            .then(|| vec![1, 2, 3, 4, top])
+            .into_iter()
+            .chain((1..=top.saturating_sub(4)).map(|low| (low..low + 5).collect::<Vec<_>>()));
+        let wild_strengths = rules
+            .wild
+            .iter()
+            .filter(|l| rules.rank.contains(**l))
+            .map(|l| rules.strength(*l))
+            .collect::<Vec<_>>();
+        windows
+            .filter(|w| w.iter().all(|s| !wild_strengths.contains(s)))
+            .rfind(|w| strengths.iter().all(|s| w.contains(s)))
+    }
 }
 
-impl Hand {
-    fn from_string(str: &str) -> Self {
-        Hand{cards: str.chars().collect::<Vec<_>>().try_into().unwrap()}
+impl Classifier for Poker {
+    // Wild cards join the biggest group of labels, or fill out a straight or
+    // flush if that's better
+    fn classify(&self, rules: &Rules, cards: &[Card]) -> HandType {
+        let by_counts = CAMEL_CARDS.classify(rules, cards);
+        if cards.len() != 5 {
+            return by_counts;
+        }
+        let shape = match (Self::best_straight(rules, cards).is_some(), Self::can_flush(rules, cards)) {
+            (true, true) => HandType::StraightFlush,
+            (true, false) => HandType::Straight,
+            (false, true) => HandType::Flush,
+            (false, false) => HandType::HighCard,
+        };
+        by_counts.max(shape)
+    }
+
+    fn strongest_is_low(&self, rules: &Rules, cards: &[Card]) -> bool {
+        matches!(self.classify(rules, cards), HandType::Straight | HandType::StraightFlush)
+            && Self::best_straight(rules, cards).is_some_and(|w| w[4] != w[3] + 1)
     }
 }
 
-type HandBid = (Hand, usize);
+// Everything that differs between the ways of playing camel cards
+#[derive(Debug, Clone)]
+struct Rules {
+    // Labels from strongest to weakest
+    rank: String,
+    // Labels that act as whatever label makes the best hand
+    wild: Vec<char>,
+    // If not empty, every card is a label followed by one of these suits
+    suits: Vec<char>,
+    tie_break: TieBreak,
+    classifier: &'static dyn Classifier,
+}
+
+impl Rules {
+    // Start from base and override it from command line options:
+    //   --rank=LABELS      labels from strongest to weakest
+    //   --wild=LABELS      wild labels
+    //   --suits=SUITS      cards are written as label and suit, e.g. Ah
+    //   --tie-break=in-order|sorted|grouped
+    //   --classifier=camel|poker
+    fn from_args<I: IntoIterator<Item = String>>(base: Rules, args: I) -> Result<Rules, String> {
+        let mut rules = base;
+        for arg in args {
+            let (key, value) = arg.split_once('=').ok_or(format!("Expected --option=value, got {arg}"))?;
+            match key {
+                "--rank" => rules.rank = value.to_string(),
+                "--wild" => rules.wild = value.chars().collect(),
+                "--suits" => rules.suits = value.chars().collect(),
+                "--tie-break" => {
+                    rules.tie_break = match value {
+                        "in-order" => TieBreak::InOrder,
+                        "sorted" => TieBreak::Sorted,
+                        "grouped" => TieBreak::Grouped,
+                        _ => return Err(format!("Unknown tie-break policy {value}")),
+                    }
+                }
+                "--classifier" => {
+                    rules.classifier = match value {
+                        "camel" => &CAMEL_CARDS,
+                        "poker" => &POKER,
+                        _ => return Err(format!("Unknown classifier {value}")),
+                    }
+                }
+                _ => return Err(format!("Unknown option {key}")),
+            }
+        }
+        Ok(rules)
+    }
+
+    fn part1() -> Rules {
+        Rules {
+            rank: "AKQJT98765432".to_string(),
+            wild: vec![],
+            suits: vec![],
+            tie_break: TieBreak::InOrder,
+            classifier: &CAMEL_CARDS,
+        }
+    }
 
-const RANK: &'static str = "AKQJT98765432";
+    // J is a joker, and the weakest card when breaking ties
+    fn part2() -> Rules {
+        Rules {
+            rank: "AKQT98765432J".to_string(),
+            wild: vec!['J'],
+            ..Rules::part1()
+        }
+    }
+
+    // Higher is stronger
+    fn strength(&self, c: char) -> usize {
+        self.rank.len() - self.rank.find(c).unwrap_or_else(|| panic!("Unknown label {c}"))
+    }
+
+    fn cards(&self, str: &str) -> Vec<Card> {
+        let chars = str.chars().collect::<Vec<_>>();
+        if self.suits.is_empty() {
+            chars.iter().map(|c| Card { label: *c, suit: None }).collect()
+        } else {
+            chars
+                .chunks(2)
+                .map(|c| match c {
+                    [label, suit] if self.suits.contains(suit) => Card {
+                        label: *label,
+                        suit: Some(*suit),
+                    },
+                    _ => panic!("Invalid card {}", c.iter().collect::<String>()),
+                })
+                .collect()
+        }
+    }
+
+    fn hand(&self, str: &str) -> Hand {
+        self.hand_of(self.cards(str))
+    }
+
+    fn hand_of(&self, cards: Vec<Card>) -> Hand {
+        let mut strengths = cards.iter().map(|c| self.strength(c.label)).collect::<Vec<_>>();
+        if self.classifier.strongest_is_low(self, &cards) {
+            let top = self.rank.chars().count();
+            strengths.iter_mut().filter(|s| **s == top).for_each(|s| *s = 0);
+        }
+        match self.tie_break {
+            TieBreak::InOrder => (),
+            TieBreak::Sorted => strengths.sort_by(|a, b| b.cmp(a)),
+            TieBreak::Grouped => {
+                let mut counts = HashMap::new();
+                strengths.iter().for_each(|k| *counts.entry(*k).or_insert(0) += 1);
+                strengths.sort_by(|a, b| (counts[b], b).cmp(&(counts[a], a)));
+            }
+        }
+        let hand_type = self.hand_type(&cards);
+        Hand {
+            key: SortKey::new(hand_type, &strengths),
+            hand_type,
+            cards,
+        }
+    }
+
+    fn hand_type(&self, cards: &[Card]) -> HandType {
+        self.classifier.classify(self, cards)
+    }
+}
 
 #[derive(Debug)]
-enum HandType {
-    FiveOfAKind(char), // Five of a kind, where all five cards have the same label: AAAAA
-    FourOfAKind(char), // Four of a kind, where four cards have the same label and one card has a different label: AA8AA
-    FullHouse(char, char), // Full house, where three cards have the same label, and the remaining two cards share a different label: 23332
-    ThreeOfAKind(char), // Three of a kind, where three cards have the same label, and the remaining two cards are each different from any other card in the hand: TTT98
-    TwoPair(char, char), // Two pair, where two cards share one label, two other cards share a second label, and the remaining card has a third label: 23432
-    OnePair(char), // One pair, where two cards share one label, and the other three cards have a different label from the pair and each other: A23A4
-    HighCard,      // High card, where all cards' labels are distinct: 23456
+struct Hand {
+    cards: Vec<Card>,
+    hand_type: HandType,
+    key: SortKey,
 }
 
-impl HandType {
+// Hand type, then card strengths in tie-break order.  Usually packed into an
+// integer so comparing hands is one comparison, but kept as a list for hands
+// too long or ranks too big to fit.
+#[derive(Debug, Clone)]
+enum SortKey {
+    Packed(u128),
+    Long(u8, Vec<usize>),
+}
+
+impl SortKey {
+    const TYPE_BITS: u32 = 4;
+    const CARD_BITS: u32 = 6;
+    const MAX_CARDS: usize = ((u128::BITS - Self::TYPE_BITS) / Self::CARD_BITS) as usize;
 
-    fn get_num(&self) -> char {
+    // Strengths are left aligned and only zero for a low ace in a five-card
+    // straight, so a hand that runs out of cards first is weaker, the same as
+    // comparing them as lists
+    fn new(hand_type: HandType, strengths: &[usize]) -> SortKey {
+        if strengths.len() > Self::MAX_CARDS || strengths.iter().any(|s| *s >= 1 << Self::CARD_BITS) {
+            return SortKey::Long(hand_type.get_num(), strengths.to_vec());
+        }
+        let mut key = hand_type.get_num() as u128;
+        for i in 0..Self::MAX_CARDS {
+            key = key << Self::CARD_BITS | strengths.get(i).copied().unwrap_or(0) as u128;
+        }
+        SortKey::Packed(key)
+    }
+
+    fn unpack(&self) -> (u8, Vec<usize>) {
         match self {
-            Self::FiveOfAKind(c) => '6',
-            Self::FourOfAKind(c) => '5',
-            Self::FullHouse(c1, _c2) => '4',
-            Self::ThreeOfAKind(c) => '3',
-            Self::TwoPair(c1, _c2) => '2',
-            Self::OnePair(c) => '1',
-            Self::HighCard => '0'
+            SortKey::Packed(key) => {
+                let mask = (1 << Self::CARD_BITS) - 1;
+                let mut strengths = (0..Self::MAX_CARDS)
+                    .rev()
+                    .map(|i| (key >> (i as u32 * Self::CARD_BITS) & mask) as usize)
+                    .collect::<Vec<_>>();
+                while strengths.last() == Some(&0) {
+                    strengths.pop();
+                }
+                ((key >> (Self::MAX_CARDS as u32 * Self::CARD_BITS)) as u8, strengths)
+            }
+            SortKey::Long(hand_type, strengths) => (*hand_type, strengths.clone()),
         }
     }
+}
 
+impl Ord for SortKey {
     fn cmp(&self, other: &Self) -> Ordering {
-        self.get_num().cmp(&other.get_num())
+        match (self, other) {
+            (SortKey::Packed(a), SortKey::Packed(b)) => a.cmp(b),
+            _ => self.unpack().cmp(&other.unpack()),
+        }
     }
+}
 
-    fn of_hand(h: &Hand) -> Self {
-        let mut counter = HashMap::new();
+impl PartialOrd for SortKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for SortKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for SortKey {}
+
+impl std::fmt::Display for Hand {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for c in &self.cards {
+            write!(f, "{}", c.label)?;
+            if let Some(suit) = c.suit {
+                write!(f, "{suit}")?;
+            }
+        }
+        Ok(())
+    }
+}
 
-        // Populate counter
-        h.cards.iter().for_each(|c| *counter.entry(c).or_insert(0) += 1);
+type HandBid = (Hand, usize);
 
-        let mut rev_counter = counter.iter().map(|(k, v)| (v, k)).collect::<Vec<(_, _)>>();
-        rev_counter.sort_by_key(|(k, _v)| -**k);
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum HandType {
+    FiveOfAKind,   // Five of a kind, where all five cards have the same label: AAAAA
+    StraightFlush, // Straight flush, a straight where all five cards have the same suit: 9h8h7h6h5h
+    FourOfAKind,   // Four of a kind, where four cards have the same label and one card has a different label: AA8AA
+    FullHouse,     // Full house, where three cards have the same label, and the remaining two cards share a different label: 23332
+    Flush,         // Flush, where all five cards have the same suit: Ah9h7h4h2h
+    Straight,      // Straight, where five distinct labels are next to each other in the rank: 98765
+    ThreeOfAKind,  // Three of a kind, where three cards have the same label, and the remaining two cards are each different from any other card in the hand: TTT98
+    TwoPair,       // Two pair, where two cards share one label, two other cards share a second label, and the remaining card has a third label: 23432
+    OnePair,       // One pair, where two cards share one label, and the other three cards have a different label from the pair and each other: A23A4
+    HighCard,      // High card, where all cards' labels are distinct: 23456
+}
 
-        //dbg!(&rev_counter);
+impl HandType {
 
-        match rev_counter.as_slice() {
-            [(5, c)] => Self::FiveOfAKind(***c),
-            [(4, c), ..] => Self::FourOfAKind(***c),
-            [(3, c1), (2, c2)] => Self::FullHouse(***c1, ***c2),
-            [(3, c), ..] => Self::ThreeOfAKind(***c),
-            [(2, c1), (2, c2), ..] => Self::TwoPair(***c1, ***c2),
-            [(2, c), ..] => Self::OnePair(***c),
-            [(1, _), ..] => Self::HighCard,
+    // Camel cards never makes straights or flushes, so one order works for both games
+    fn get_num(&self) -> u8 {
+        match self {
+            Self::FiveOfAKind => 9,
+            Self::StraightFlush => 8,
+            Self::FourOfAKind => 7,
+            Self::FullHouse => 6,
+            Self::Flush => 5,
+            Self::Straight => 4,
+            Self::ThreeOfAKind => 3,
+            Self::TwoPair => 2,
+            Self::OnePair => 1,
+            Self::HighCard => 0
+        }
+    }
+
+    // From the number of cards with each label, largest first.  Hands of
+    // other sizes go by their biggest groups.
+    fn of_counts(counts: &[usize]) -> Self {
+        match counts {
+            [n, ..] if *n >= 5 => Self::FiveOfAKind,
+            [4, ..] => Self::FourOfAKind,
+            [3, n, ..] if *n >= 2 => Self::FullHouse,
+            [3, ..] => Self::ThreeOfAKind,
+            [2, 2, ..] => Self::TwoPair,
+            [2, ..] => Self::OnePair,
+            [1, ..] => Self::HighCard,
             _ => panic!("format error"),
         }
     }
 }
 
-impl Hand {
-    fn get_type(&self) -> HandType {
-        HandType::of_hand(self)
+impl Ord for HandType {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.get_num().cmp(&other.get_num())
+    }
+}
+
+impl PartialOrd for HandType {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
     }
+}
 
+impl Ord for Hand {
     fn cmp(&self, other: &Self) -> Ordering {
-        match self.get_type().cmp(&other.get_type()) {
-            Ordering::Equal => {
-                // find first different card
-                match self.cards.iter().zip(other.cards.iter()).find(|(c1, c2)| c1 != c2).map(|(c1, c2)| RANK.find(*c2).unwrap().cmp(&RANK.find(*c1).unwrap())) {
-                    Some(o) => o,
-                    None => Ordering::Equal
-                }
-            }
-            o => o
-        }
+        self.key.cmp(&other.key)
+    }
+}
+
+impl PartialOrd for Hand {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
     }
 }
 
-fn parse(stdin: std::io::Stdin) -> Vec<HandBid> {
+impl PartialEq for Hand {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Hand {}
+
+fn parse(stdin: std::io::Stdin) -> Vec<(String, usize)> {
     stdin
         .lines()
         .map(|line| line.unwrap())
         .map(
             |l| match l.split_ascii_whitespace().collect::<Vec<_>>().as_slice() {
-                [hand, bid] => (Hand::from_string(hand), bid.parse::<usize>().unwrap()),
+                [hand, bid] => (hand.to_string(), bid.parse::<usize>().unwrap()),
                 _ => panic!("format error"),
             },
         )
         .collect()
 }
 
+fn play(name: &str, rules: &Rules, lines: &[(String, usize)]) -> Vec<HandBid> {
+    let mut handbids: Vec<HandBid> = lines.iter().map(|(h, b)| (rules.hand(h), *b)).collect();
+
+    handbids.sort_by(|(h1, _), (h2, _)| h1.cmp(h2));
+
+    if let Some((best, _)) = handbids.last() {
+        println!("{name} strongest hand: {best} ({:?})", best.hand_type);
+    }
+
+    let winnings = handbids.iter().enumerate().map(|(i, (_, b))| b * (i + 1)).sum::<usize>();
+    println!("{name} total winnings: {winnings}");
+
+    handbids
+}
+
+// Every five-card hand that can be made from the rank's labels
+struct HandSpace {
+    // Sort keys of all the hands, weakest first
+    keys: Vec<SortKey>,
+    // Number of hands of each type, strongest type first
+    counts: Vec<(HandType, usize)>,
+}
+
+impl HandSpace {
+    fn new(rules: &Rules) -> Result<HandSpace, String> {
+        if !rules.suits.is_empty() {
+            return Err("Hand statistics only cover hands without suits".to_string());
+        }
+        let labels = rules.rank.chars().collect::<Vec<_>>();
+        let mut keys = vec![];
+        let mut counts = HashMap::new();
+        for mut i in 0..labels.len().pow(5) {
+            let mut cards = vec![];
+            for _ in 0..5 {
+                cards.push(Card {
+                    label: labels[i % labels.len()],
+                    suit: None,
+                });
+                i /= labels.len();
+            }
+            let hand = rules.hand_of(cards);
+            *counts.entry(hand.hand_type).or_insert(0) += 1;
+            keys.push(hand.key);
+        }
+        keys.sort();
+
+        let mut counts = counts.into_iter().collect::<Vec<_>>();
+        counts.sort_by(|(t1, _), (t2, _)| t2.cmp(t1));
+        Ok(HandSpace { keys, counts })
+    }
+
+    fn weaker(&self, hand: &Hand) -> usize {
+        self.keys.partition_point(|k| *k < hand.key)
+    }
+
+    fn stronger(&self, hand: &Hand) -> usize {
+        self.keys.len() - self.keys.partition_point(|k| *k <= hand.key)
+    }
+
+    // Share of hands below this one, counting equal hands as half below
+    fn percentile(&self, hand: &Hand) -> f64 {
+        let ties = self.keys.len() - self.weaker(hand) - self.stronger(hand);
+        100.0 * (self.weaker(hand) as f64 + ties as f64 / 2.0) / self.keys.len() as f64
+    }
+
+    fn beat_probability(&self, hand: &Hand) -> f64 {
+        self.weaker(hand) as f64 / self.keys.len() as f64
+    }
+
+    // Expected change in total winnings from adding a uniformly random hand
+    // with this bid to the sorted handbids.  The new hand moves up a rank
+    // for every hand it doesn't lose to, and pushes up every other hand.
+    fn expected_insertion(&self, handbids: &[HandBid], bid: usize) -> f64 {
+        let total = self.keys.len() as f64;
+        let pushed = handbids
+            .iter()
+            .map(|(h, b)| {
+                let loses = self.weaker(h) as f64 / total;
+                bid as f64 * (1.0 - loses) + *b as f64 * loses
+            })
+            .sum::<f64>();
+        bid as f64 + pushed
+    }
+}
+
+// Rank of hand and change in total winnings when it's added to the sorted
+// handbids, after any hands equal to it
+fn insertion(handbids: &[HandBid], hand: &Hand, bid: usize) -> (usize, usize) {
+    let before = handbids.partition_point(|(h, _)| h <= hand);
+    let pushed = handbids[before..].iter().map(|(_, b)| b).sum::<usize>();
+    (before + 1, bid * (before + 1) + pushed)
+}
+
+fn report_stats(name: &str, rules: &Rules, handbids: &[HandBid], query: Option<&(String, usize)>) {
+    let space = HandSpace::new(rules).unwrap_or_else(|e| panic!("{e}"));
+    println!("{name} hands of each type, out of {}:", space.keys.len());
+    for (hand_type, count) in &space.counts {
+        println!("  {hand_type:?}: {count}");
+    }
+
+    if let Some((cards, bid)) = query {
+        let hand = rules.hand(cards);
+        println!(
+            "{name} {hand} ({:?}): percentile {:.2}, beats a random hand with probability {:.4}",
+            hand.hand_type,
+            space.percentile(&hand),
+            space.beat_probability(&hand)
+        );
+        let (rank, gain) = insertion(handbids, &hand, *bid);
+        println!("{name} adding {hand} with bid {bid}: rank {rank}, winnings +{gain}");
+        println!(
+            "{name} adding a random hand with bid {bid}: winnings +{:.1} on average",
+            space.expected_insertion(handbids, *bid)
+        );
+    }
+}
+
 fn main() {
-    let mut handbids = parse(std::io::stdin());
+    let mut args = std::env::args().skip(1).collect::<Vec<_>>();
 
-    /*
-    let hand_types = handbids
+    // --stats reports on the whole hand space, and --stats=HAND,BID also on
+    // one hand and what it would win
+    let stats = args
         .iter()
-        .map(|(h, _)| h.get_type())
-        .collect::<Vec<_>>();
+        .position(|a| a == "--stats" || a.starts_with("--stats="))
+        .map(|i| {
+            let arg = args.remove(i);
+            arg.strip_prefix("--stats=").map(|query| match query.split_once(',') {
+                Some((hand, bid)) => (hand.to_string(), bid.parse::<usize>().unwrap_or_else(|e| panic!("{e}"))),
+                None => panic!("Expected --stats=HAND,BID, got {arg}"),
+            })
+        });
+
+    let lines = parse(std::io::stdin());
 
-    println!("hand types: {:?}", hand_types);
-    */
+    // Any options describe a custom set of rules, on top of part 1's
+    let games = if !args.is_empty() {
+        let rules = Rules::from_args(Rules::part1(), args).unwrap_or_else(|e| panic!("{e}"));
+        vec![("Custom", rules)]
+    } else {
+        vec![("Part 1", Rules::part1()), ("Part 2", Rules::part2())]
+    };
 
-    handbids.sort_by(|(h1,_), (h2,_)| h1.cmp(h2));
+    for (name, rules) in games {
+        let handbids = play(name, &rules, &lines);
+        if let Some(query) = &stats {
+            report_stats(name, &rules, &handbids, query.as_ref());
+        }
+    }
+}
 
-    println!("hands: {:?}", handbids);
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    let handbids = handbids.iter().enumerate().map(|(i, (h, b))| (h, b*(i+1))).collect::<Vec<_>>();
+    impl Rules {
+        // The best type the cards can make, trying every choice of labels for
+        // the wild cards.  The order they're picked in doesn't matter, so
+        // picks only go from weaker labels to stronger ones.
+        fn hand_type_by_enumeration(&self, cards: &[Card]) -> HandType {
+            let fixed = cards.iter().map(|c| c.label).filter(|l| !self.wild.contains(l)).collect::<Vec<_>>();
+            let non_wild = self.rank.chars().filter(|c| !self.wild.contains(c)).collect::<Vec<_>>();
+            let non_wild = &non_wild;
+
+            (fixed.len()..cards.len())
+                .fold(vec![(fixed, 0)], |hands, _| {
+                    hands
+                        .into_iter()
+                        .flat_map(|(hand, from)| {
+                            (from..non_wild.len()).map(move |i| {
+                                let mut hand = hand.clone();
+                                hand.push(non_wild[i]);
+                                (hand, i)
+                            })
+                        })
+                        .collect()
+                })
+                .iter()
+                .map(|(hand, _)| HandType::of_cards(hand))
+                .max()
+                .unwrap()
+        }
+    }
 
-    println!("total winnings: {}", handbids.iter().map(|(_, b)| b).sum::<usize>())
+    impl HandType {
+        fn of_cards(cards: &[char]) -> Self {
+            let mut counter = HashMap::new();
+
+            // Populate counter
+            cards.iter().for_each(|c| *counter.entry(c).or_insert(0) += 1);
+
+            let mut counts = counter.into_values().collect::<Vec<_>>();
+            counts.sort_by(|a, b| b.cmp(a));
+
+            Self::of_counts(&counts)
+        }
+    }
+
+    // Every five-card hand of labels from the rank
+    fn all_hands(rules: &Rules) -> impl Iterator<Item = Vec<Card>> + '_ {
+        let labels = rules.rank.chars().collect::<Vec<_>>();
+        (0..labels.len().pow(5)).map(move |mut i| {
+            let mut cards = vec![];
+            for _ in 0..5 {
+                cards.push(Card {
+                    label: labels[i % labels.len()],
+                    suit: None,
+                });
+                i /= labels.len();
+            }
+            cards
+        })
+    }
+
+    #[test]
+    fn wheel_is_the_lowest_straight() {
+        for tie_break in ["in-order", "sorted", "grouped"] {
+            let args = ["--suits=hdcs", "--classifier=poker", &format!("--tie-break={tie_break}")];
+            let rules = Rules::from_args(Rules::part1(), args.map(String::from)).unwrap();
+            let wheel = rules.hand("2h3d4c5sAh");
+            assert_eq!(wheel.hand_type, HandType::Straight);
+            assert!(wheel < rules.hand("2h3d4c5s6h"), "{tie_break}");
+            assert!(wheel > rules.hand("AhAdKc7s2h"), "{tie_break}");
+        }
+
+        // A wild card makes the best straight it can, which isn't the wheel
+        let args = ["--suits=hdcs", "--classifier=poker", "--tie-break=sorted", "--wild=J"];
+        let rules = Rules::from_args(Rules::part1(), args.map(String::from)).unwrap();
+        assert!(rules.hand("2h3d4c5sAh") < rules.hand("2h3d4c5sJh"));
+    }
+
+    #[test]
+    fn wild_cards_join_the_biggest_group() {
+        let rules = Rules::part2();
+        // Enumeration only depends on which labels are in the hand
+        let mut enumerated = HashMap::new();
+        for cards in all_hands(&rules) {
+            let mut labels = cards.iter().map(|c| c.label).collect::<Vec<_>>();
+            labels.sort();
+            let slow = *enumerated
+                .entry(labels)
+                .or_insert_with(|| rules.hand_type_by_enumeration(&cards));
+            assert_eq!(
+                rules.hand_type(&cards),
+                slow,
+                "{}",
+                cards.iter().map(|c| c.label).collect::<String>()
+            );
+        }
+    }
 }
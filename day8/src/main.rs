@@ -1,84 +1,409 @@
 //extern crate regex;
-type Dir = char;
+use std::collections::HashMap;
 
-type Network = std::collections::HashMap<String, (String, String)>;
+// Index into the instruction alphabet, which is also the branch to take
+type Dir = usize;
+
+type Node = usize;
+
+// Nodes are interned to ids and every node has one branch per instruction,
+// stored side by side so a step is a single index
+struct Network {
+    names: Vec<String>,
+    ids: HashMap<String, Node>,
+    width: usize,
+    branches: Vec<Node>,
+}
+
+impl Network {
+    fn new(width: usize) -> Network {
+        Network {
+            names: vec![],
+            ids: HashMap::new(),
+            width,
+            branches: vec![],
+        }
+    }
+
+    fn intern(&mut self, name: &str) -> Node {
+        if let Some(id) = self.ids.get(name) {
+            return *id;
+        }
+        let id = self.names.len();
+        self.names.push(name.to_string());
+        self.ids.insert(name.to_string(), id);
+        self.branches.extend(std::iter::repeat_n(Node::MAX, self.width));
+        id
+    }
+
+    fn id(&self, name: &str) -> Option<Node> {
+        self.ids.get(name).copied()
+    }
+
+    fn name(&self, node: Node) -> &str {
+        &self.names[node]
+    }
+
+    fn next(&self, node: Node, dir: Dir) -> Node {
+        self.branches[node * self.width + dir]
+    }
+
+    fn nodes(&self) -> std::ops::Range<Node> {
+        0..self.names.len()
+    }
+}
 
 struct Info {
+    alphabet: Vec<char>,
     directions: Vec<Dir>,
     network: Network,
 }
 
+// The input can start with a line like "alphabet: LRUD" giving the
+// instructions, in the order of each node's branches.  Otherwise it's LR.
 fn parse(stdio: std::io::Stdin) -> Info {
     let mut lines = stdio.lines().map(|l| l.unwrap());
-    let directions = lines.next().unwrap().chars().collect();
+    let mut first = lines.next().unwrap();
+    let alphabet = match first.strip_prefix("alphabet:") {
+        Some(alphabet) => {
+            let alphabet = alphabet.trim().chars().collect::<Vec<_>>();
+            first = lines.next().unwrap();
+            alphabet
+        }
+        None => vec!['L', 'R'],
+    };
+    let directions = first
+        .chars()
+        .map(|c| alphabet.iter().position(|a| *a == c).unwrap_or_else(|| panic!("Invalid direction {c}")))
+        .collect();
 
     let lines = lines.skip(1);
 
-    let mut m = Network::new();
+    let mut network = Network::new(alphabet.len());
 
-    let re = regex::Regex::new(r"(\w+)\s*=\s*\((\w+),\s*(\w+)\)").unwrap();
-    let captures = lines.map(|l| {
-        re.captures(&l).unwrap().extract::<3>().1.map(|s| s.to_owned())
-    });
-    captures.for_each(|[k, v1, v2]| {
-        m.insert(k, (v1, v2));
-    });
+    let re = regex::Regex::new(r"(\w+)\s*=\s*\(([^)]*)\)").unwrap();
+    for l in lines {
+        let (_, [k, vs]) = re.captures(&l).unwrap_or_else(|| panic!("format error: {l}")).extract();
+        let vs = vs.split(',').map(|v| v.trim()).collect::<Vec<_>>();
+        if vs.len() != alphabet.len() {
+            panic!("{k} has {} branches, but there are {} instructions", vs.len(), alphabet.len());
+        }
+        let k = network.intern(k);
+        for (dir, v) in vs.iter().enumerate() {
+            let v = network.intern(v);
+            network.branches[k * network.width + dir] = v;
+        }
+    }
+
+    if let Some(node) = network.nodes().find(|n| network.next(*n, 0) == Node::MAX) {
+        panic!("{} is never defined", network.name(node));
+    }
+
+    Info { alphabet, directions, network }
+}
+
+// Picks out nodes by name
+#[derive(Debug)]
+enum Pattern {
+    Exact(String),
+    Prefix(String),
+    Suffix(String),
+    Regex(regex::Regex),
+}
 
-    Info{directions, network: m}
+impl Pattern {
+    // NAME, prefix:TEXT, suffix:TEXT or regex:REGEX
+    fn from_string(s: &str) -> Result<Pattern, String> {
+        Ok(match s.split_once(':') {
+            Some(("prefix", text)) => Pattern::Prefix(text.to_string()),
+            Some(("suffix", text)) => Pattern::Suffix(text.to_string()),
+            Some(("regex", re)) => Pattern::Regex(regex::Regex::new(re).map_err(|e| e.to_string())?),
+            Some((kind, _)) => return Err(format!("Unknown pattern kind {kind}")),
+            None => Pattern::Exact(s.to_string()),
+        })
+    }
+
+    fn matches(&self, name: &str) -> bool {
+        match self {
+            Pattern::Exact(s) => name == s,
+            Pattern::Prefix(s) => name.starts_with(s.as_str()),
+            Pattern::Suffix(s) => name.ends_with(s.as_str()),
+            Pattern::Regex(re) => re.is_match(name),
+        }
+    }
+}
+
+// Where walks start and which nodes they're trying to reach
+#[derive(Debug)]
+struct Query {
+    starts: Pattern,
+    goals: Pattern,
 }
 
-fn part1(info: &Info, init_str: &str, p2: bool) -> usize {
-    let directions = info.directions.iter().cycle();
+impl Query {
+    fn part1() -> Query {
+        Query {
+            starts: Pattern::Exact("AAA".to_string()),
+            goals: Pattern::Exact("ZZZ".to_string()),
+        }
+    }
 
-    let init = info.network.get(init_str).unwrap();
+    fn part2() -> Query {
+        Query {
+            starts: Pattern::Suffix("A".to_string()),
+            goals: Pattern::Suffix("Z".to_string()),
+        }
+    }
 
-    directions.scan(init, |state, dir| {
-        let next_name = match dir {
-            'L' => &state.0,
-            'R' => &state.1,
-            _ => panic!("Invalid direction"),
-        };
-        match next_name.as_str() {
-            s if s.ends_with('Z') && p2 => None,
-            "ZZZ" => None,
-            _ => {
-                let next = info.network.get(next_name).unwrap();
-                *state = next;
-                Some(next)
+    // Start from base and override it from command line options:
+    //   --from=PATTERN     start nodes
+    //   --to=PATTERN       goal nodes
+    fn from_args<I: IntoIterator<Item = String>>(base: Query, args: I) -> Result<Query, String> {
+        let mut query = base;
+        for arg in args {
+            let (key, value) = arg.split_once('=').ok_or(format!("Expected --option=value, got {arg}"))?;
+            match key {
+                "--from" => query.starts = Pattern::from_string(value)?,
+                "--to" => query.goals = Pattern::from_string(value)?,
+                _ => return Err(format!("Unknown option {key}")),
             }
         }
-    }).count() + 1
+        Ok(query)
+    }
+
+    // Start nodes in name order
+    fn starts(&self, network: &Network) -> Vec<Node> {
+        let mut starts = network.nodes().filter(|n| self.starts.matches(network.name(*n))).collect::<Vec<_>>();
+        starts.sort_by_key(|n| network.name(*n));
+        starts
+    }
+
+    // Whether each node is a goal
+    fn goals(&self, network: &Network) -> Vec<bool> {
+        network.nodes().map(|n| self.goals.matches(network.name(n))).collect()
+    }
+}
+
+// How a walk first gets to a goal
+#[derive(Debug)]
+struct Route {
+    init: Node,
+    steps: usize,
+    goal: Node,
+}
+
+impl Route {
+    // Every node on the way, from the start to the goal
+    fn visits(&self, info: &Info) -> Vec<Node> {
+        let mut visits = vec![self.init];
+        for step in 0..self.steps {
+            let dir = info.directions[step % info.directions.len()];
+            visits.push(info.network.next(*visits.last().unwrap(), dir));
+        }
+        visits
+    }
+}
+
+// The first time the walk from init moves onto a goal, or how it loops
+// forever without getting to one.  If there's a goal, it comes before the
+// walk runs out of new states, so only a walk that gets that far needs the
+// full cycle search to show how it loops.
+fn route(info: &Info, init: Node, goals: &[bool]) -> Result<Route, Walk> {
+    let states = info.network.names.len() * info.directions.len();
+    let mut node = init;
+    for (step, dir) in info.directions.iter().cycle().take(states).enumerate() {
+        node = info.network.next(node, *dir);
+        if goals[node] {
+            return Ok(Route {
+                init,
+                steps: step + 1,
+                goal: node,
+            });
+        }
+    }
+    let walk = walk(info, init, goals);
+    debug_assert_eq!(walk.first_goal(), None);
+    Err(walk)
+}
+
+fn part1(info: &Info) -> usize {
+    let query = Query::part1();
+    let init = info.network.id("AAA").unwrap_or_else(|| panic!("No node AAA"));
+    match route(info, init, &query.goals(&info.network)) {
+        Ok(r) => r.steps,
+        Err(_) => panic!("AAA never reaches ZZZ"),
+    }
+}
+
+// A walk goes through states of (node, index into the directions).  There
+// are finitely many, so the walk runs through a prefix and then goes round
+// the same cycle forever.
+#[derive(Debug)]
+struct Walk {
+    // Steps before the cycle starts
+    prefix: usize,
+    // Steps to go round the cycle once
+    cycle: usize,
+    // Steps at which the walk is on a goal, before the cycle starts
+    prefix_hits: Vec<usize>,
+    // Steps at which the walk is on a goal the first time round the cycle.
+    // It's there again every `cycle` steps after.
+    cycle_hits: Vec<usize>,
+}
+
+fn walk(info: &Info, init: Node, goals: &[bool]) -> Walk {
+    // Step at which each state was first seen
+    let mut seen = vec![usize::MAX; info.network.names.len() * info.directions.len()];
+    let mut hits = vec![];
+    let mut node = init;
+    let mut step = 0;
+
+    let prefix = loop {
+        let index = step % info.directions.len();
+        let state = &mut seen[node * info.directions.len() + index];
+        if *state != usize::MAX {
+            break *state;
+        }
+        *state = step;
+        if goals[node] {
+            hits.push(step);
+        }
+        node = info.network.next(node, info.directions[index]);
+        step += 1;
+    };
+
+    let (prefix_hits, cycle_hits) = hits.iter().partition(|h| **h < prefix);
+    Walk {
+        prefix,
+        cycle: step - prefix,
+        prefix_hits,
+        cycle_hits,
+    }
 }
 
-type P2state<'a> = Vec<&'a (String, String)>;
+// The steps start, start + step, start + 2 * step, ... or just start if step
+// is zero
+#[derive(Debug, Clone, Copy)]
+struct Progression {
+    start: u128,
+    step: u128,
+}
 
-fn part2(info: &Info) -> usize {
-    let inits = info.network.iter().filter(|(k, _)| k.ends_with('A')).map(|(_k, v)| v).collect::<P2state>();
+impl Progression {
+    fn contains(&self, t: u128) -> bool {
+        match self.step {
+            0 => t == self.start,
+            step => t >= self.start && (t - self.start).is_multiple_of(step),
+        }
+    }
 
-    //dbg!(&inits);
+    // Generalized CRT: solve t = a (mod m) and t = b (mod n) for moduli that
+    // needn't be coprime, then take the first solution in both progressions
+    fn intersect(&self, other: &Progression) -> Option<Progression> {
+        if self.step == 0 {
+            return other.contains(self.start).then_some(*self);
+        }
+        if other.step == 0 {
+            return self.contains(other.start).then_some(*other);
+        }
 
-    let directions = info.directions.iter().cycle();
+        let (m, n) = (self.step as i128, other.step as i128);
+        let num::integer::ExtendedGcd { gcd, x, .. } = num::Integer::extended_gcd(&m, &n);
+        let diff = other.start as i128 - self.start as i128;
+        if diff % gcd != 0 {
+            return None;
+        }
+        let lcm = m / gcd * n;
+        // self.start + m * k is a solution when m * k = diff (mod n)
+        let k = (diff / gcd % (n / gcd)) * (x % (n / gcd)) % (n / gcd);
+        let t = (self.start as i128 + m * k).rem_euclid(lcm) as u128;
+
+        let lowest = self.start.max(other.start);
+        let lcm = lcm as u128;
+        let start = if t >= lowest { t } else { t + (lowest - t).div_ceil(lcm) * lcm };
+        Some(Progression { start, step: lcm })
+    }
+}
 
-    directions.scan(inits, |state, dir| {
-        let next_names = state.iter().map(|(v1, v2)| {
-            match dir {
-                'L' => v1,
-                'R' => v2,
-                _ => panic!("Invalid direction"),
-            }            
-        }).collect::<Vec<&String>>();
+impl Walk {
+    // The first step after setting off that the walk is on a goal
+    fn first_goal(&self) -> Option<usize> {
+        let hits = self.prefix_hits.iter().chain(&self.cycle_hits);
+        hits.copied()
+            .find(|h| *h > 0)
+            .or(self.cycle_hits.first().map(|h| h + self.cycle))
+    }
 
-        /*if next_names.iter().any(|s| s.ends_with('Z')) {
-            dbg!(&next_names);
-        }*/
+    // Every step the walk is on a goal
+    fn hits(&self) -> Vec<Progression> {
+        let once = self.prefix_hits.iter().map(|h| Progression {
+            start: *h as u128,
+            step: 0,
+        });
+        let repeating = self.cycle_hits.iter().map(|h| Progression {
+            start: *h as u128,
+            step: self.cycle as u128,
+        });
+        once.chain(repeating).collect()
+    }
+}
 
-        if next_names.iter().all(|s| s.ends_with('Z')) { None }
-        else {
-            let next_state = next_names.iter().map(|n| info.network.get(*n).unwrap()).collect::<P2state>();
-            *state = next_state;
-            Some(())
+// Drops candidates that others already cover, so they don't multiply with
+// every walk.  Progressions with the same step and residue only differ in
+// where they start, and the earliest covers the rest.  Single steps go if a
+// progression covers them.  Nothing else can go, even a candidate that
+// starts late, because later walks may rule out all the earlier ones.
+fn simplify(candidates: Vec<Progression>) -> Vec<Progression> {
+    let mut progressions: HashMap<(u128, u128), u128> = HashMap::new();
+    let mut points = std::collections::BTreeSet::new();
+    for c in candidates {
+        if c.step == 0 {
+            points.insert(c.start);
+        } else {
+            let start = progressions.entry((c.start % c.step, c.step)).or_insert(c.start);
+            *start = (*start).min(c.start);
         }
-    }).count() + 1
+    }
+
+    let progressions = progressions
+        .into_iter()
+        .map(|((_, step), start)| Progression { start, step })
+        .collect::<Vec<_>>();
+    let points = points
+        .into_iter()
+        .filter(|p| !progressions.iter().any(|c| c.contains(*p)))
+        .map(|start| Progression { start, step: 0 })
+        .collect::<Vec<_>>();
+    progressions.into_iter().chain(points).collect()
+}
+
+// Finds the first step after setting off where every walk is on a goal,
+// without assuming anything about how the walks are laid out.  Each walk can
+// be on goals at several points of its cycle, so the candidates are every
+// combination of one hit from each walk.  Starting on goals doesn't count,
+// the same as for first_goal.
+fn part2(info: &Info, query: &Query) -> Result<u128, String> {
+    let goals = query.goals(&info.network);
+    let walks = query.starts(&info.network).into_iter().map(|n| walk(info, n, &goals));
+
+    let candidates = walks.fold(
+        vec![Progression { start: 1, step: 1 }],
+        |candidates, walk| {
+            let hits = walk.hits();
+            simplify(
+                candidates
+                    .iter()
+                    .flat_map(|c| hits.iter().filter_map(|h| c.intersect(h)))
+                    .collect(),
+            )
+        },
+    );
+
+    candidates
+        .iter()
+        .map(|c| c.start)
+        .min()
+        .ok_or("The walks are never all on goals at the same time".to_string())
 }
 
 /* Cool, my brute force approach didn't work.  Let's use lcm.  I hate mind-reading aspects of AoC.
@@ -86,17 +411,160 @@ fn part2(info: &Info) -> usize {
    See also https://www.reddit.com/r/adventofcode/comments/18dfpub/2023_day_8_part_2_why_is_spoiler_correct/
 */
 fn part2_f_aoc(info: &Info) -> usize {
-    let inits = info.network.iter().filter(|(k, _)| k.ends_with('A')).map(|(k, _v)| k); //.collect::<P2state>();
+    let query = Query::part2();
+    let goals = query.goals(&info.network);
+
+    let t = query.starts(&info.network).into_iter().map(|n| match route(info, n, &goals) {
+        Ok(r) => r.steps,
+        Err(_) => panic!("{} never reaches a Z node", info.network.name(n)),
+    });
+
+    t.reduce(num::integer::lcm).unwrap()
+
+}
 
-    let t = inits.map(|s| part1(&info, s, true));
+// Nodes from which some instructions lead to a goal
+fn can_reach_goal(network: &Network, goals: &[bool]) -> Vec<bool> {
+    let mut sources = vec![vec![]; network.names.len()];
+    for node in network.nodes() {
+        for dir in 0..network.width {
+            sources[network.next(node, dir)].push(node);
+        }
+    }
+
+    let mut reached = goals.to_vec();
+    let mut todo = network.nodes().filter(|n| goals[*n]).collect::<Vec<_>>();
+    while let Some(node) = todo.pop() {
+        for source in &sources[node] {
+            if !reached[*source] {
+                reached[*source] = true;
+                todo.push(*source);
+            }
+        }
+    }
+    reached
+}
+
+// The shortest instructions that put every start on a goal at the same time.
+// Starts that meet walk together from then on, so this is a breadth first
+// search over sets of nodes, skipping sets with a node that can't get to any
+// goal.  It gives up after exploring max_sets sets.
+fn synthesize(info: &Info, query: &Query, max_sets: usize) -> Result<Vec<Dir>, String> {
+    let network = &info.network;
+    let goals = query.goals(network);
+    let alive = can_reach_goal(network, &goals);
+
+    let mut init = query.starts(network);
+    init.sort();
+    if init.iter().any(|n| !alive[*n]) {
+        return Err("Some starts can never reach a goal".to_string());
+    }
+
+    // Every set found, with the set it came from and the instruction taken
+    let mut sets = vec![(init.clone(), usize::MAX, 0)];
+    let mut seen = HashMap::from([(init, 0)]);
+    let mut next = 0;
+    while next < sets.len() {
+        if sets[next].0.iter().all(|n| goals[*n]) {
+            let mut instructions = vec![];
+            while sets[next].1 != usize::MAX {
+                instructions.push(sets[next].2);
+                next = sets[next].1;
+            }
+            instructions.reverse();
+            return Ok(instructions);
+        }
+        if sets.len() > max_sets {
+            return Err(format!("Gave up after exploring {max_sets} sets of nodes"));
+        }
 
-    t.reduce(|a, b| num::integer::lcm(a,b)).unwrap()
+        for dir in 0..network.width {
+            let mut set = sets[next].0.iter().map(|n| network.next(*n, dir)).collect::<Vec<_>>();
+            set.sort();
+            set.dedup();
+            if set.iter().any(|n| !alive[*n]) || seen.contains_key(&set) {
+                continue;
+            }
+            seen.insert(set.clone(), sets.len());
+            sets.push((set, next, dir));
+        }
+        next += 1;
+    }
+
+    Err("No instructions lead every start to a goal at the same time".to_string())
+}
 
+// Where every start of the query gets to, and when they're all on goals
+fn explore(info: &Info, query: &Query, show_visits: bool) {
+    let network = &info.network;
+    for start in query.starts(network) {
+        match route(info, start, &query.goals(network)) {
+            Ok(r) => {
+                println!("{} reaches {} in {} steps", network.name(start), network.name(r.goal), r.steps);
+                if show_visits {
+                    let names = r.visits(info).iter().map(|n| network.name(*n)).collect::<Vec<_>>();
+                    println!("  {}", names.join(" "));
+                }
+            }
+            Err(w) => println!(
+                "{} never reaches a goal: it loops every {} steps after {}",
+                network.name(start), w.cycle, w.prefix
+            ),
+        }
+    }
+    match part2(info, query) {
+        Ok(steps) => println!("All on goals together after {steps} steps"),
+        Err(e) => println!("{e}"),
+    }
 }
 
 fn main() {
     let info = parse(std::io::stdin());
+    let (flags, args): (Vec<String>, Vec<String>) = std::env::args().skip(1).partition(|a| !a.contains('='));
+    if let Some(flag) = flags.iter().find(|f| !["--walks", "--visits", "--synthesize"].contains(&f.as_str())) {
+        panic!("Unknown option {flag}");
+    }
+
+    // --walks shows how each ghost's walk is laid out
+    if flags.iter().any(|a| a == "--walks") {
+        let query = Query::part2();
+        let goals = query.goals(&info.network);
+        for init in query.starts(&info.network) {
+            let w = walk(&info, init, &goals);
+            println!(
+                "{}: prefix {}, cycle {}, Z at {:?} then {:?} every {} steps",
+                info.network.name(init), w.prefix, w.cycle, w.prefix_hits, w.cycle_hits, w.cycle
+            );
+        }
+    }
+
+    // --synthesize finds the shortest instructions that put every start on a
+    // goal at once, by default for part 2's starts and goals
+    if flags.iter().any(|a| a == "--synthesize") {
+        let query = Query::from_args(Query::part2(), args).unwrap_or_else(|e| panic!("{e}"));
+        match synthesize(&info, &query, 1_000_000) {
+            Ok(instructions) => println!(
+                "Shortest instructions ({} steps): {}",
+                instructions.len(),
+                instructions.iter().map(|d| info.alphabet[*d]).collect::<String>()
+            ),
+            Err(e) => println!("{e}"),
+        }
+        return;
+    }
+
+    // --from and --to ask about a custom set of walks, and --visits lists
+    // the nodes on each
+    if !args.is_empty() {
+        let query = Query::from_args(Query::part1(), args).unwrap_or_else(|e| panic!("{e}"));
+        explore(&info, &query, flags.iter().any(|a| a == "--visits"));
+        return;
+    }
 
-    println!("Part 1: {}", part1(&info, "AAA", false));
-    println!("Part 2: {}", part2_f_aoc(&info));
+    println!("Part 1: {}", part1(&info));
+    println!("Part 2 (LCM shortcut): {}", part2_f_aoc(&info));
+    match part2(&info, &Query::part2()) {
+        Ok(steps) => println!("Part 2: {steps}"),
+        Err(e) => println!("Part 2: {e}"),
+    }
 }
@@ -1,37 +1,158 @@
-type Seq = Vec<i32>;
+use num::{BigInt, BigRational, One, Zero};
+
+type Seq = Vec<i64>;
 
 fn parse(stdin: std::io::Stdin) -> impl Iterator<Item = Seq> {
     stdin.lines().map(|l| l.unwrap()).map(|l| {
         l.split_ascii_whitespace()
-            .map(|n| n.parse::<i32>().unwrap())
-            .collect::<Vec<i32>>()
+            .map(|n| n.parse::<i64>().unwrap())
+            .collect::<Vec<i64>>()
     })
 }
 
-fn extrapolate(seq: &Seq) -> i32 {
-    if seq.iter().all(|e| *e == 0) {
-        0
-    } else {
-        let derivative = seq
-            .windows(2)
-            .map(|window| window[1] - window[0])
-            .collect::<Seq>();
-        let e = extrapolate(&derivative);
-        seq.last().unwrap() + e
+// The polynomial through a sequence, where the first value is at x = 0
+#[derive(Debug)]
+struct Polynomial {
+    // Coefficient of x^i at index i, with no trailing zeros
+    coefficients: Vec<BigRational>,
+    // Number of values it was fitted to
+    len: usize,
+}
+
+impl Polynomial {
+    // Newton's forward differences: the sequence is sum(d_k * C(x, k)) where
+    // d_k is the first of the k-th differences.  The differences have to
+    // reach all zeros with at least one value left, or nothing shows the
+    // sequence is a polynomial at all.
+    fn fit(seq: &Seq) -> Result<Polynomial, String> {
+        let mut leading = vec![];
+        let mut row = seq.iter().map(|n| BigInt::from(*n)).collect::<Vec<_>>();
+        while !row.iter().all(|n| n.is_zero()) {
+            if row.len() == 1 {
+                return Err(format!(
+                    "{seq:?} needs degree {} to fit its {} values, so it isn't a polynomial",
+                    seq.len() - 1,
+                    seq.len()
+                ));
+            }
+            leading.push(row[0].clone());
+            row = row.windows(2).map(|w| &w[1] - &w[0]).collect();
+        }
+        if row.is_empty() {
+            return Err("Empty sequence".to_string());
+        }
+
+        // Expand C(x, k) = x (x - 1) ... (x - k + 1) / k! into powers of x
+        let mut coefficients = vec![BigRational::zero(); leading.len()];
+        let mut falling = vec![BigRational::one()];
+        for (k, d) in leading.iter().enumerate() {
+            let scale = BigRational::from(d.clone()) / BigRational::from(factorial(k));
+            for (c, f) in coefficients.iter_mut().zip(&falling) {
+                *c += &scale * f;
+            }
+            // Multiply falling by (x - k)
+            let mut next = vec![BigRational::zero(); falling.len() + 1];
+            for (i, f) in falling.iter().enumerate() {
+                next[i + 1] += f;
+                next[i] -= f * BigRational::from(BigInt::from(k));
+            }
+            falling = next;
+        }
+        while coefficients.last().is_some_and(|c| c.is_zero()) {
+            coefficients.pop();
+        }
+
+        Ok(Polynomial {
+            coefficients,
+            len: seq.len(),
+        })
+    }
+
+    // The zero polynomial counts as degree 0
+    fn degree(&self) -> usize {
+        self.coefficients.len().saturating_sub(1)
+    }
+
+    fn at(&self, x: i64) -> BigInt {
+        let x = BigRational::from(BigInt::from(x));
+        let value = self
+            .coefficients
+            .iter()
+            .rev()
+            .fold(BigRational::zero(), |acc, c| acc * &x + c);
+        // Integer values at every fitted x make it integer everywhere
+        assert!(value.is_integer(), "Non-integer value {value}");
+        value.to_integer()
+    }
+
+    // Positive offsets count steps after the last value and negative ones
+    // steps before the first
+    fn extrapolate(&self, offset: i64) -> BigInt {
+        if offset >= 0 {
+            self.at(self.len as i64 - 1 + offset)
+        } else {
+            self.at(offset)
+        }
     }
 }
 
+impl std::fmt::Display for Polynomial {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let terms = self
+            .coefficients
+            .iter()
+            .enumerate()
+            .rev()
+            .filter(|(_, c)| !c.is_zero())
+            .map(|(i, c)| match i {
+                0 => format!("{c}"),
+                1 => format!("({c})x"),
+                _ => format!("({c})x^{i}"),
+            })
+            .collect::<Vec<_>>();
+        match terms.is_empty() {
+            true => write!(f, "0"),
+            false => write!(f, "{}", terms.join(" + ")),
+        }
+    }
+}
+
+fn factorial(k: usize) -> BigInt {
+    (1..=k).map(BigInt::from).product()
+}
+
 fn main() {
     let seqs = parse(std::io::stdin());
-    let seqs = seqs.collect::<Vec<_>>();
 
-    let p1 = seqs.iter().map(|s| extrapolate(&s)).sum::<i32>();
+    // Blank lines add nothing, and a sequence that doesn't fit is reported
+    // and left out
+    let mut polys = vec![];
+    for (i, seq) in seqs.enumerate().filter(|(_, s)| !s.is_empty()) {
+        match Polynomial::fit(&seq) {
+            Ok(p) => polys.push((i + 1, p)),
+            Err(e) => println!("Line {}: {e}", i + 1),
+        }
+    }
+
+    for arg in std::env::args().skip(1) {
+        match arg.split_once('=') {
+            // --fit lists each sequence's polynomial
+            None if arg == "--fit" => {
+                for (line, p) in &polys {
+                    println!("{line}: degree {}: {p}", p.degree());
+                }
+            }
+            // --offset=K sums the values K steps past the ends
+            Some(("--offset", k)) => {
+                let k = k.parse::<i64>().unwrap_or_else(|e| panic!("{e}"));
+                println!("offset {k}: {}", polys.iter().map(|(_, p)| p.extrapolate(k)).sum::<BigInt>());
+            }
+            _ => panic!("Unknown option {arg}"),
+        }
+    }
 
-    let p2 = seqs.iter().map(|s| {
-        let mut rev = s.clone();
-        rev.reverse();
-        extrapolate(rev.as_ref())
-    }).sum::<i32>();
+    let p1 = polys.iter().map(|(_, p)| p.extrapolate(1)).sum::<BigInt>();
+    let p2 = polys.iter().map(|(_, p)| p.extrapolate(-1)).sum::<BigInt>();
 
     println!("p1: {p1} p2: {p2}");
 }